@@ -81,6 +81,10 @@ bool StlThumbCreator::create(const QString &path, int width, int height, QImage
     {
         pic = s2t::extract_3mf_preview(path.toStdString().c_str(), width, height);
     }
+    else if (mime_type.inherits("application/x-ufp") || file_ext == "ufp")
+    {
+        pic = s2t::extract_ufp_preview(path.toStdString().c_str(), width, height);
+    }
 
     // failed?
     if (!pic.data)
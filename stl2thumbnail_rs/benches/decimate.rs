@@ -0,0 +1,55 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use stl2thumbnail::stl::{
+    decimate::decimate,
+    mesh::{Mesh, Triangle, Vec3},
+    rasterbackend::RasterBackend,
+};
+
+const SIZE: u32 = 256;
+
+/// A uv sphere with about a million triangles, far more than there are pixels
+fn sphere() -> Mesh {
+    let n = 700;
+    let point = |i: usize, j: usize| {
+        let theta = std::f32::consts::PI * i as f32 / n as f32;
+        let phi = 2.0 * std::f32::consts::PI * j as f32 / n as f32;
+        Vec3::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()) * 50.0
+    };
+
+    let mut triangles = vec![];
+    for i in 0..n {
+        for j in 0..n {
+            let [a, b, c, d] = [point(i, j), point(i + 1, j), point(i + 1, j + 1), point(i, j + 1)];
+            triangles.push(Triangle::new([a, b, c], (b - a).cross(&(c - a)).normalize()));
+            triangles.push(Triangle::new([a, c, d], (c - a).cross(&(d - a)).normalize()));
+        }
+    }
+
+    Mesh::new(triangles)
+}
+
+/// Decimation pays off once the simplified mesh is rendered several times (turntables),
+/// a single frame is faster without it
+fn still(c: &mut Criterion) {
+    let mesh = sphere();
+    let backend = RasterBackend::new(SIZE, SIZE);
+    let (aabb, scale) = backend.fit_mesh_scale(&mesh);
+    let cell_size = aabb.size().max() / SIZE as f32;
+
+    let mut group = c.benchmark_group("still");
+    group.sample_size(10);
+
+    group.bench_function("render", |b| b.iter(|| backend.render(&mesh, scale, &aabb, None)));
+    group.bench_function("decimate + render", |b| {
+        b.iter(|| backend.render(&decimate(&mesh, cell_size), scale, &aabb, None))
+    });
+    group.bench_function("render decimated", |b| {
+        let decimated = decimate(&mesh, cell_size);
+        b.iter(|| backend.render(&decimated, scale, &aabb, None))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, still);
+criterion_main!(benches);
@@ -57,8 +57,8 @@ extern "C" {
 PictureBuffer render_stl(const char *path, RenderSettings settings);
 
 /// Extracts the thumbnail embedded into the gcode
-/// If there are multiple thumbnails, the one with
-/// the highest resolution is returned
+/// If there are multiple thumbnails, the smallest one
+/// that does not need to be upscaled is returned
 ///
 /// Free the buffer with free_picture_buffer
 ///
@@ -74,6 +74,14 @@ PictureBuffer extract_gcode_preview(const char *path, uint32_t width, uint32_t h
 /// path has to be a valid pointer to a null terminated string
 PictureBuffer extract_3mf_preview(const char *path, uint32_t width, uint32_t height);
 
+/// Extracts the thumbnail embedded into the ufp file
+///
+/// Free the buffer with free_picture_buffer
+///
+/// # Safety
+/// path has to be a valid pointer to a null terminated string
+PictureBuffer extract_ufp_preview(const char *path, uint32_t width, uint32_t height);
+
 /// Frees the memory of a PictureBuffer
 void free_picture_buffer(PictureBuffer *buffer);
 
@@ -0,0 +1,281 @@
+use std::{
+    collections::HashMap,
+    io::{Cursor, Read},
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{bail, Result};
+use roxmltree::Node;
+
+use crate::stl::mesh::{transform_point, Facet, Mat4, Mesh, Triangle, Vec3};
+
+// ref: ISO/ASTM 52915
+
+pub fn read_mesh_from_file<P: AsRef<Path>>(filename: P) -> Result<Mesh> {
+    let data = std::fs::read(filename)?;
+    read_mesh_from_data(&data)
+}
+
+/// Reads an AMF mesh, either as plain XML or zip compressed
+///
+/// Colors of volumes, materials and objects are attached to the triangles.
+pub fn read_mesh_from_data(data: &[u8]) -> Result<Mesh> {
+    if data.starts_with(b"PK\x03\x04") {
+        // the archive contains a single amf file
+        let mut archive = zip::ZipArchive::new(Cursor::new(data))?;
+
+        let Some(name) = archive.file_names().find(|n| n.to_ascii_lowercase().ends_with(".amf")) else {
+            bail!("cannot find amf in archive")
+        };
+        let name = name.to_string();
+
+        let mut content = String::new();
+        archive.by_name(&name)?.read_to_string(&mut content)?;
+
+        return parse_amf(&content);
+    }
+
+    parse_amf(std::str::from_utf8(data)?)
+}
+
+fn parse_amf(content: &str) -> Result<Mesh> {
+    let doc = roxmltree::Document::parse(content)?;
+    let root = doc.root_element();
+
+    if !root.has_tag_name("amf") {
+        bail!("not an AMF file");
+    }
+
+    // everything is converted to millimeters
+    let scale = match root.attribute("unit").unwrap_or("millimeter") {
+        "millimeter" => 1.0,
+        "inch" => 25.4,
+        "feet" => 304.8,
+        "meter" => 1000.0,
+        "micron" => 0.001,
+        unit => bail!("unknown unit '{unit}'"),
+    };
+
+    let materials: HashMap<&str, Vec3> = root
+        .children()
+        .filter(|n| n.has_tag_name("material"))
+        .filter_map(|n| Some((n.attribute("id")?, read_color(&n)?)))
+        .collect();
+
+    let objects: HashMap<&str, Node> = root
+        .children()
+        .filter(|n| n.has_tag_name("object"))
+        .filter_map(|n| Some((n.attribute("id")?, n)))
+        .collect();
+
+    // the constellations place the objects on the build plate,
+    // without any we render the objects as they are
+    let instances: Vec<_> = root
+        .descendants()
+        .filter(|n| n.has_tag_name("instance"))
+        .filter_map(|n| Some((*objects.get(n.attribute("objectid")?)?, instance_transform(&n))))
+        .collect();
+
+    let mut triangles = Mesh::default();
+    let identity = Mat4::identity();
+
+    if instances.is_empty() {
+        for object in root.children().filter(|n| n.has_tag_name("object")) {
+            read_object(&object, &identity, scale, &materials, &mut triangles)?;
+        }
+    } else {
+        for (object, transform) in instances {
+            read_object(&object, &transform, scale, &materials, &mut triangles)?;
+        }
+    }
+
+    Ok(triangles)
+}
+
+fn read_object(
+    object: &Node,
+    transform: &Mat4,
+    scale: f32,
+    materials: &HashMap<&str, Vec3>,
+    triangles: &mut Mesh,
+) -> Result<()> {
+    let Some(mesh) = object.children().find(|n| n.has_tag_name("mesh")) else {
+        return Ok(());
+    };
+
+    let transform = Mat4::identity().append_scaling(scale) * transform;
+
+    let vertices = mesh
+        .children()
+        .filter(|n| n.has_tag_name("vertices"))
+        .flat_map(|n| n.children().filter(|n| n.has_tag_name("vertex")))
+        .map(|vertex| {
+            let Some(coordinates) = vertex.children().find(|n| n.has_tag_name("coordinates")) else {
+                bail!("vertex without coordinates")
+            };
+            let (Some(x), Some(y), Some(z)) = (
+                child_value(&coordinates, "x"),
+                child_value(&coordinates, "y"),
+                child_value(&coordinates, "z"),
+            ) else {
+                bail!("invalid vertex coordinates")
+            };
+
+            Ok(transform_point(&transform, &Vec3::new(x, y, z)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let object_color = read_color(object);
+
+    for volume in mesh.children().filter(|n| n.has_tag_name("volume")) {
+        // the most specific color wins
+        let color = read_color(&volume)
+            .or_else(|| materials.get(volume.attribute("materialid")?).copied())
+            .or(object_color);
+
+        for triangle in volume.children().filter(|n| n.has_tag_name("triangle")) {
+            // indices are non-negative integers, anything else is rejected
+            let (Some(v1), Some(v2), Some(v3)) = (
+                child_value::<usize>(&triangle, "v1"),
+                child_value::<usize>(&triangle, "v2"),
+                child_value::<usize>(&triangle, "v3"),
+            ) else {
+                bail!("invalid triangle")
+            };
+
+            let corners = [v1, v2, v3];
+            if corners.iter().any(|&i| i >= vertices.len()) {
+                bail!("vertex index out of range");
+            }
+
+            let vertices = corners.map(|i| vertices[i]);
+            let normal = (vertices[1] - vertices[0])
+                .cross(&(vertices[2] - vertices[0]))
+                .normalize();
+
+            let mut facet = Facet::from(Triangle::new(vertices, normal));
+            if let Some(color) = color {
+                facet = facet.with_colors([color; 3]);
+            }
+
+            triangles.push(facet);
+        }
+    }
+
+    Ok(())
+}
+
+/// Translation (deltax, deltay, deltaz) and rotation (rx, ry, rz in degrees) of an instance
+fn instance_transform(instance: &Node) -> Mat4 {
+    let value = |tag| child_value::<f32>(instance, tag).unwrap_or_default();
+
+    let translation = Vec3::new(value("deltax"), value("deltay"), value("deltaz"));
+
+    glm::translation(&translation)
+        * glm::rotation(value("rz").to_radians(), &Vec3::new(0.0, 0.0, 1.0))
+        * glm::rotation(value("ry").to_radians(), &Vec3::new(0.0, 1.0, 0.0))
+        * glm::rotation(value("rx").to_radians(), &Vec3::new(1.0, 0.0, 0.0))
+}
+
+fn read_color(node: &Node) -> Option<Vec3> {
+    let color = node.children().find(|n| n.has_tag_name("color"))?;
+
+    Some(Vec3::new(
+        child_value(&color, "r")?,
+        child_value(&color, "g")?,
+        child_value(&color, "b")?,
+    ))
+}
+
+fn child_value<T: FromStr>(node: &Node, tag: &str) -> Option<T> {
+    node.children()
+        .find(|n| n.has_tag_name(tag))?
+        .text()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    const QUAD_AMF: &[u8] = include_bytes!("../test_models/quad.amf");
+
+    #[test]
+    fn read_amf_test() {
+        let mesh = read_mesh_from_data(QUAD_AMF).unwrap();
+
+        assert_eq!(mesh.len(), 2);
+
+        // instance moved by 10mm along x
+        assert_eq!(mesh[0].vertices[0], Vec3::new(10.0, 0.0, 0.0));
+        assert_eq!(mesh[0].vertices[1], Vec3::new(11.0, 0.0, 0.0));
+        assert_eq!(mesh[0].normal, Vec3::new(0.0, 0.0, 1.0));
+
+        // material color and volume color
+        assert_eq!(mesh.facet(0).colors, Some([Vec3::new(1.0, 0.0, 0.0); 3]));
+        assert_eq!(mesh.facet(1).colors, Some([Vec3::new(0.0, 0.0, 1.0); 3]));
+    }
+
+    #[test]
+    fn read_zipped_amf_test() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(vec![]));
+        writer
+            .start_file("quad.amf", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(QUAD_AMF).unwrap();
+        let data = writer.finish().unwrap().into_inner();
+
+        let mesh = read_mesh_from_data(&data).unwrap();
+
+        assert_eq!(mesh.len(), 2);
+    }
+
+    #[test]
+    fn read_amf_units_test() {
+        let amf = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <amf unit="inch">
+              <object id="0">
+                <mesh>
+                  <vertices>
+                    <vertex><coordinates><x>0</x><y>0</y><z>0</z></coordinates></vertex>
+                    <vertex><coordinates><x>1</x><y>0</y><z>0</z></coordinates></vertex>
+                    <vertex><coordinates><x>0</x><y>1</y><z>0</z></coordinates></vertex>
+                  </vertices>
+                  <volume><triangle><v1>0</v1><v2>1</v2><v3>2</v3></triangle></volume>
+                </mesh>
+              </object>
+            </amf>"#;
+
+        let mesh = read_mesh_from_data(amf.as_bytes()).unwrap();
+
+        assert_eq!(mesh[0].vertices[1], Vec3::new(25.4, 0.0, 0.0));
+        assert_eq!(mesh.facet(0).colors, None);
+    }
+
+    #[test]
+    fn read_amf_invalid_index_test() {
+        for index in ["1.5", "-1", "x"] {
+            let amf = format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+                <amf>
+                  <object id="0">
+                    <mesh>
+                      <vertices>
+                        <vertex><coordinates><x>0</x><y>0</y><z>0</z></coordinates></vertex>
+                        <vertex><coordinates><x>1</x><y>0</y><z>0</z></coordinates></vertex>
+                        <vertex><coordinates><x>0</x><y>1</y><z>0</z></coordinates></vertex>
+                      </vertices>
+                      <volume><triangle><v1>0</v1><v2>{index}</v2><v3>2</v3></triangle></volume>
+                    </mesh>
+                  </object>
+                </amf>"#
+            );
+
+            assert!(read_mesh_from_data(amf.as_bytes()).is_err(), "{index}");
+        }
+    }
+}
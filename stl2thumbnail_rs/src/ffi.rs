@@ -1,6 +1,6 @@
-use std::{ffi::CStr, mem::forget, os::raw::c_char};
+use std::{ffi::CStr, mem::forget, os::raw::c_char, time::Duration};
 
-use crate::{gcode, stl::parser::Parser, stl::rasterbackend::RasterBackend, threemf};
+use crate::{gcode, picture::Picture, stl::parser::Parser, stl::rasterbackend::RasterBackend, threemf, ufp};
 
 #[repr(C)]
 pub struct PictureBuffer {
@@ -60,43 +60,23 @@ pub unsafe extern "C" fn render_stl(path: *const c_char, settings: RenderSetting
                     backend.render_options.draw_size_hint = settings.size_hint;
                     backend.render_options.grid_visible = settings.grid;
 
-                    // render
-                    let pic = backend.render(&mesh, scale, &aabb, None);
+                    // render, a coarse preview is returned if the timeout expires
+                    let timeout = (settings.timeout > 0).then(|| Duration::from_millis(settings.timeout));
+                    let pic = backend.render(&mesh, scale, &aabb, timeout).picture;
 
-                    let boxed_data = pic.data_as_boxed_slice();
-                    let data = boxed_data.as_ptr();
-                    let len = pic.data().len() as u32;
-
-                    // leak the memory owned by boxed_data
-                    forget(boxed_data);
-
-                    return PictureBuffer {
-                        data,
-                        len,
-                        stride: pic.stride(),
-                        depth: pic.depth(),
-                        width: pic.width(),
-                        height: pic.height(),
-                    };
+                    return picture_to_buffer(pic);
                 }
             }
         }
     }
 
-    PictureBuffer {
-        data: std::ptr::null(),
-        len: 0,
-        stride: 0,
-        depth: 0,
-        width: 0,
-        height: 0,
-    }
+    null_buffer()
 }
 
 #[no_mangle]
 /// Extracts the thumbnail embedded into the gcode
-/// If there are multiple thumbnails, the one with
-/// the highest resolution is returned
+/// If there are multiple thumbnails, the smallest one
+/// that does not need to be upscaled is returned
 ///
 /// Free the buffer with free_picture_buffer
 ///
@@ -107,38 +87,17 @@ pub unsafe extern "C" fn extract_gcode_preview(path: *const c_char, width: u32,
         let path = CStr::from_ptr(path).to_str();
 
         if let Ok(path) = path {
-            if let Ok(mut previews) = gcode::extract_previews_from_file(path) {
-                if let Some(pic) = previews.last_mut() {
+            if let Ok(previews) = gcode::extract_previews_from_file(path) {
+                if let Some(mut pic) = gcode::select_preview(previews, width, height) {
                     pic.resize_keep_aspect_ratio(width, height);
 
-                    let boxed_data = pic.data_as_boxed_slice();
-                    let data = boxed_data.as_ptr();
-                    let len = boxed_data.len() as u32;
-
-                    // leak the memory owned by boxed_data
-                    forget(boxed_data);
-
-                    return PictureBuffer {
-                        data,
-                        len,
-                        stride: pic.stride(),
-                        depth: pic.depth(),
-                        width: pic.width(),
-                        height: pic.height(),
-                    };
+                    return picture_to_buffer(pic);
                 }
             }
         }
     }
 
-    PictureBuffer {
-        data: std::ptr::null(),
-        len: 0,
-        stride: 0,
-        depth: 0,
-        width: 0,
-        height: 0,
-    }
+    null_buffer()
 }
 
 #[no_mangle]
@@ -156,25 +115,57 @@ pub unsafe extern "C" fn extract_3mf_preview(path: *const c_char, width: u32, he
             if let Ok(mut pic) = threemf::extract_preview_from_file(path) {
                 pic.resize_keep_aspect_ratio(width, height);
 
-                let boxed_data = pic.data_as_boxed_slice();
-                let data = boxed_data.as_ptr();
-                let len = boxed_data.len() as u32;
-
-                // leak the memory owned by boxed_data
-                forget(boxed_data);
-
-                return PictureBuffer {
-                    data,
-                    len,
-                    stride: pic.stride(),
-                    depth: pic.depth(),
-                    width: pic.width(),
-                    height: pic.height(),
-                };
+                return picture_to_buffer(pic);
+            }
+        }
+    }
+
+    null_buffer()
+}
+
+#[no_mangle]
+/// Extracts the thumbnail embedded into the ufp file
+///
+/// Free the buffer with free_picture_buffer
+///
+/// # Safety
+/// path has to be a valid pointer to a null terminated string
+pub unsafe extern "C" fn extract_ufp_preview(path: *const c_char, width: u32, height: u32) -> PictureBuffer {
+    if !path.is_null() {
+        let path = CStr::from_ptr(path).to_str();
+
+        if let Ok(path) = path {
+            if let Ok(mut pic) = ufp::extract_preview_from_file(path) {
+                pic.resize_keep_aspect_ratio(width, height);
+
+                return picture_to_buffer(pic);
             }
         }
     }
 
+    null_buffer()
+}
+
+/// Hands the pixels over to the caller, they are freed by free_picture_buffer
+fn picture_to_buffer(pic: Picture) -> PictureBuffer {
+    let boxed_data = pic.data_as_boxed_slice();
+    let data = boxed_data.as_ptr();
+    let len = boxed_data.len() as u32;
+
+    // leak the memory owned by boxed_data
+    forget(boxed_data);
+
+    PictureBuffer {
+        data,
+        len,
+        stride: pic.stride(),
+        depth: pic.depth(),
+        width: pic.width(),
+        height: pic.height(),
+    }
+}
+
+fn null_buffer() -> PictureBuffer {
     PictureBuffer {
         data: std::ptr::null(),
         len: 0,
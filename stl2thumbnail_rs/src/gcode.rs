@@ -6,6 +6,7 @@ use std::{
 use anyhow::{bail, Result};
 use base64::{engine::general_purpose::STANDARD, Engine as _};
 use byteorder::{LittleEndian, ReadBytesExt};
+use serde::Serialize;
 
 use crate::picture::Picture;
 
@@ -47,6 +48,7 @@ impl FileHeader {
 struct Block {
     kind: BlockType,
     compression: CompressionType,
+    params: Vec<u8>,
     data: Vec<u8>,
 }
 
@@ -55,14 +57,204 @@ impl Block {
         match self.compression {
             CompressionType::None => Ok(self.data.clone()),
             CompressionType::Deflate => {
-                let mut decompress = flate2::Decompress::new(false);
+                // libbgcode uses zlib, i.e., deflate with a zlib header
+                let mut decoder = flate2::read::ZlibDecoder::new(self.data.as_slice());
                 let mut decompressed_data = vec![];
-                decompress.decompress_vec(&self.data, &mut decompressed_data, flate2::FlushDecompress::None)?;
+                decoder.read_to_end(&mut decompressed_data)?;
                 Ok(decompressed_data)
             }
             _ => bail!("unimplemented"),
         }
     }
+
+    fn is_metadata(&self) -> bool {
+        matches!(
+            self.kind,
+            BlockType::FileMetadata | BlockType::PrinterMetadata | BlockType::PrintMetadata | BlockType::SlicerMetadata
+        )
+    }
+}
+
+/// Print information written by the slicer
+///
+/// All fields are optional as every slicer reports a different subset.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct Metadata {
+    /// slicer that generated the file
+    pub producer: Option<String>,
+    pub printer_model: Option<String>,
+    pub filament_type: Option<String>,
+    /// estimated printing time in seconds (normal mode)
+    pub estimated_printing_time: Option<u64>,
+    /// filament length in mm
+    pub filament_used_mm: Option<f32>,
+    /// filament volume in cm³
+    pub filament_used_cm3: Option<f32>,
+    /// filament weight in g
+    pub filament_used_g: Option<f32>,
+    pub filament_cost: Option<f32>,
+    /// layer height in mm
+    pub layer_height: Option<f32>,
+    /// nozzle diameter in mm
+    pub nozzle_diameter: Option<f32>,
+}
+
+impl Metadata {
+    fn apply(&mut self, key: &str, value: &str) {
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim();
+
+        if value.is_empty() {
+            return;
+        }
+
+        match key.as_str() {
+            "producer" => self.producer = Some(value.to_string()),
+            "printer_model" => self.printer_model = Some(value.to_string()),
+            "filament_type" => self.filament_type = Some(value.to_string()),
+            // Prusa, Orca: '38m 21s', Bambu: '1h 2m 3s', Cura: seconds
+            "estimated printing time (normal mode)" | "estimated printing time" | "total estimated time" | "time" => {
+                self.estimated_printing_time = parse_duration(value).or(self.estimated_printing_time)
+            }
+            "filament used [mm]" | "total filament length [mm]" => {
+                self.filament_used_mm = parse_sum(value).or(self.filament_used_mm)
+            }
+            "filament used [cm3]" => self.filament_used_cm3 = parse_sum(value).or(self.filament_used_cm3),
+            "filament used [g]" | "total filament used [g]" | "total filament weight [g]" => {
+                self.filament_used_g = parse_sum(value).or(self.filament_used_g)
+            }
+            // Cura reports the filament length in meters
+            "filament used" => {
+                self.filament_used_mm = parse_sum(value.trim_end_matches('m'))
+                    .map(|m| m * 1000.0)
+                    .or(self.filament_used_mm)
+            }
+            "filament cost" | "total filament cost" => self.filament_cost = parse_sum(value).or(self.filament_cost),
+            "layer_height" | "layer height" => self.layer_height = value.parse().ok().or(self.layer_height),
+            "nozzle_diameter" => self.nozzle_diameter = parse_first(value).or(self.nozzle_diameter),
+            _ => (),
+        }
+    }
+}
+
+impl Metadata {
+    /// Short summary of the print time, filament weight and layer height, e.g., '1h38m 6.5g 0.2mm'
+    pub fn summary(&self) -> String {
+        let mut parts = vec![];
+
+        if let Some(seconds) = self.estimated_printing_time {
+            let (hours, minutes) = (seconds / 3600, seconds % 3600 / 60);
+
+            parts.push(match (hours, minutes) {
+                (0, 0) => format!("{seconds}s"),
+                (0, _) => format!("{minutes}m"),
+                _ => format!("{hours}h{minutes}m"),
+            });
+        }
+
+        if let Some(weight) = self.filament_used_g {
+            parts.push(format!("{weight:.1}g"));
+        }
+
+        if let Some(layer_height) = self.layer_height {
+            parts.push(format!("{layer_height}mm"));
+        }
+
+        parts.join(" ")
+    }
+}
+
+/// Draws the print time, filament weight and layer height in a bar at the bottom of the picture
+pub fn draw_metadata(pic: &mut Picture, metadata: &Metadata) {
+    let text = metadata.summary();
+
+    if text.is_empty() {
+        return;
+    }
+
+    pic.draw_caption(&text);
+}
+
+/// Parses durations like '1d 2h 3m 4s' or plain seconds
+fn parse_duration(s: &str) -> Option<u64> {
+    if let Ok(seconds) = s.parse::<f32>() {
+        return Some(seconds as u64);
+    }
+
+    let mut seconds = 0;
+    for part in s.split_whitespace() {
+        let (value, unit) = part.split_at(part.find(|c: char| !c.is_ascii_digit())?);
+        let value: u64 = value.parse().ok()?;
+
+        seconds += match unit {
+            "d" => value * 86400,
+            "h" => value * 3600,
+            "m" => value * 60,
+            "s" => value,
+            _ => return None,
+        };
+    }
+
+    Some(seconds)
+}
+
+/// Parses a comma separated list of numbers (one per extruder) and returns their sum
+fn parse_sum(s: &str) -> Option<f32> {
+    s.split(',').map(|v| v.trim().parse::<f32>().ok()).sum()
+}
+
+/// Parses the first value of a comma separated list (one per extruder)
+fn parse_first(s: &str) -> Option<f32> {
+    s.split(',').next()?.trim().parse().ok()
+}
+
+/// Dimensions and format of an embedded preview
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PreviewInfo {
+    pub width: u32,
+    pub height: u32,
+    /// 'png', 'jpg', 'qoi' or 'unknown'
+    pub format: String,
+}
+
+/// A preview as stored in the gcode file (not yet decoded)
+struct EncodedPreview {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl EncodedPreview {
+    fn info(&self) -> PreviewInfo {
+        let format = image::guess_format(&self.data)
+            .map(|f| f.extensions_str()[0].to_string())
+            .unwrap_or("unknown".to_string());
+
+        PreviewInfo {
+            width: self.width,
+            height: self.height,
+            format,
+        }
+    }
+
+    fn decode(&self) -> Result<Picture> {
+        let img = image::load_from_memory(&self.data)?;
+        Ok(Picture::from_img_buffer(img.to_rgba8()))
+    }
+}
+
+/// Picks the smallest preview that does not need to be upscaled to fit into a rectangle of width and height
+///
+/// Falls back to the largest preview if all previews are smaller than requested.
+pub fn select_preview(mut previews: Vec<Picture>, width: u32, height: u32) -> Option<Picture> {
+    previews.sort_by_key(|p| p.width() * p.height());
+
+    let index = previews
+        .iter()
+        .position(|p| p.width() >= width || p.height() >= height)
+        .unwrap_or(previews.len().checked_sub(1)?);
+
+    Some(previews.swap_remove(index))
 }
 
 fn detect_format(data: &[u8]) -> Result<GCodeType> {
@@ -100,7 +292,28 @@ pub fn extract_previews_from_data(data: &[u8]) -> Result<Vec<Picture>> {
 }
 
 pub fn extract_previews_binary(data: &[u8]) -> Result<Vec<Picture>> {
-    let mut pictures = vec![];
+    encoded_previews_binary(data)?.iter().map(|p| p.decode()).collect()
+}
+
+pub fn list_previews_from_file<P: AsRef<Path>>(filename: P) -> Result<Vec<PreviewInfo>> {
+    let data = std::fs::read(filename)?;
+
+    list_previews_from_data(&data)
+}
+
+/// Lists the embedded previews without decoding them
+pub fn list_previews_from_data(data: &[u8]) -> Result<Vec<PreviewInfo>> {
+    let previews = match detect_format(data) {
+        Ok(GCodeType::Ascii) => encoded_previews_ascii(data)?,
+        Ok(GCodeType::Binary) => encoded_previews_binary(data)?,
+        _ => bail!("Cannot detect gcode format"),
+    };
+
+    Ok(previews.iter().map(|p| p.info()).collect())
+}
+
+fn encoded_previews_binary(data: &[u8]) -> Result<Vec<EncodedPreview>> {
+    let mut previews = vec![];
 
     let mut cursor = std::io::Cursor::new(data);
 
@@ -109,24 +322,95 @@ pub fn extract_previews_binary(data: &[u8]) -> Result<Vec<Picture>> {
     // skip header
     cursor.seek(std::io::SeekFrom::Start(10))?;
 
-    while let Ok(block) = try_read_thumbnail_block(&header, &mut cursor) {
-        if let Some(block) = block {
-            if let BlockType::Thumbnail = block.kind {
-                let block_data = block.uncompressed_data()?;
-                let mut block_reader = std::io::Cursor::new(block_data);
+    while let Ok(block) = try_read_block(&header, &mut cursor) {
+        if let BlockType::Thumbnail = block.kind {
+            // params: format (u16), width (u16), height (u16)
+            let mut params = std::io::Cursor::new(&block.params);
+            let _format = params.read_u16::<LittleEndian>()?;
+            let width = params.read_u16::<LittleEndian>()? as u32;
+            let height = params.read_u16::<LittleEndian>()? as u32;
+
+            previews.push(EncodedPreview {
+                width,
+                height,
+                data: block.uncompressed_data()?,
+            });
+        }
+    }
+
+    Ok(previews)
+}
 
-                let mut img_data = vec![];
-                block_reader.read_to_end(&mut img_data)?;
-                let img = image::load_from_memory(&img_data)?;
+pub fn extract_metadata_from_file<P: AsRef<Path>>(filename: P) -> Result<Metadata> {
+    let data = std::fs::read(filename)?;
 
-                pictures.push(Picture::from_img_buffer(img.to_rgba8()));
-            } else {
-                println!("not thumbnail");
+    extract_metadata_from_data(&data)
+}
+
+pub fn extract_metadata_from_data(data: &[u8]) -> Result<Metadata> {
+    match detect_format(data) {
+        Ok(GCodeType::Ascii) => extract_metadata_ascii(data),
+        Ok(GCodeType::Binary) => extract_metadata_binary(data),
+        _ => bail!("Cannot detect gcode format"),
+    }
+}
+
+pub fn extract_metadata_binary(data: &[u8]) -> Result<Metadata> {
+    let mut metadata = Metadata::default();
+
+    let mut cursor = std::io::Cursor::new(data);
+
+    let header = read_header(&mut cursor)?;
+
+    // skip header
+    cursor.seek(std::io::SeekFrom::Start(10))?;
+
+    // metadata blocks are stored as INI ('key=value' lines)
+    while let Ok(block) = try_read_block(&header, &mut cursor) {
+        if !block.is_metadata() {
+            continue;
+        }
+
+        if let Ok(block_data) = block.uncompressed_data() {
+            for line in String::from_utf8_lossy(&block_data).lines() {
+                if let Some((key, value)) = line.split_once('=') {
+                    metadata.apply(key, value);
+                }
             }
         }
     }
 
-    Ok(pictures)
+    Ok(metadata)
+}
+
+pub fn extract_metadata_ascii(data: &[u8]) -> Result<Metadata> {
+    let mut metadata = Metadata::default();
+
+    // depending on the slicer, the metadata is stored as comments at the start or the end of the file
+    //
+    // Prusa, Orca: '; key = value'
+    // Bambu: '; key: value'
+    // Cura: ';KEY:value'
+    for line in String::from_utf8_lossy(data).lines() {
+        let Some(comment) = line.trim().strip_prefix(';') else {
+            continue;
+        };
+
+        if let Some(producer) = comment.trim().strip_prefix("generated by ") {
+            metadata.producer = Some(producer.split(" on ").next().unwrap_or(producer).to_string());
+        } else if let Some((key, value)) = comment.split_once(" = ") {
+            metadata.apply(key, value);
+        } else {
+            // Bambu puts several items on a single line ('; key: value; key: value')
+            for item in comment.split("; ") {
+                if let Some((key, value)) = item.split_once(':') {
+                    metadata.apply(key, value);
+                }
+            }
+        }
+    }
+
+    Ok(metadata)
 }
 
 fn read_header<R>(reader: &mut R) -> Result<FileHeader>
@@ -146,7 +430,7 @@ where
     })
 }
 
-fn try_read_thumbnail_block<R>(file_header: &FileHeader, reader: &mut R) -> Result<Option<Block>>
+fn try_read_block<R>(file_header: &FileHeader, reader: &mut R) -> Result<Block>
 where
     R: Read + Seek,
 {
@@ -185,29 +469,14 @@ where
         _ => unimplemented!("{block_type}"),
     };
 
-    // skip parameter sections
-    match block_kind {
-        BlockType::FileMetadata => {
-            reader.read_u16::<LittleEndian>()?;
-        }
-        BlockType::GCode => {
-            reader.read_u16::<LittleEndian>()?;
-        }
-        BlockType::SlicerMetadata => {
-            reader.read_u16::<LittleEndian>()?;
-        }
-        BlockType::PrinterMetadata => {
-            reader.read_u16::<LittleEndian>()?;
-        }
-        BlockType::PrintMetadata => {
-            reader.read_u16::<LittleEndian>()?;
-        }
-        BlockType::Thumbnail => {
-            reader.read_u16::<LittleEndian>()?;
-            reader.read_u16::<LittleEndian>()?;
-            reader.read_u16::<LittleEndian>()?;
-        }
-    }
+    // read parameter section
+    // (encoding for all blocks, thumbnails additionally store format, width and height)
+    let params_size = match block_kind {
+        BlockType::Thumbnail => 6,
+        _ => 2,
+    };
+    let mut params = vec![0u8; params_size];
+    reader.read_exact(&mut params)?;
 
     // read block data section
     let mut buf = vec![0u8; block_size as usize];
@@ -219,20 +488,31 @@ where
         reader.read_u32::<LittleEndian>()?;
     }
 
-    // let mut block = None;
+    Ok(Block {
+        kind: block_kind,
+        compression: compression_type,
+        params,
+        data: buf,
+    })
+}
+
+pub fn extract_previews_ascii(data: &[u8]) -> Result<Vec<Picture>> {
+    let mut pictures = vec![];
 
-    if let BlockType::Thumbnail = block_kind {
-        return Ok(Some(Block {
-            kind: block_kind,
-            compression: compression_type,
-            data: buf,
-        }));
+    for preview in encoded_previews_ascii(data)? {
+        // try to decode the image (possible formats are 'png', 'jpeg', 'qoi')
+        if let Ok(picture) = preview.decode() {
+            pictures.push(picture);
+        }
     }
 
-    Ok(None)
+    // sort by size (ascending order)
+    pictures.sort_by_key(|a| a.width() * a.height());
+
+    Ok(pictures)
 }
 
-pub fn extract_previews_ascii(data: &[u8]) -> Result<Vec<Picture>> {
+fn encoded_previews_ascii(data: &[u8]) -> Result<Vec<EncodedPreview>> {
     let content = String::from_utf8(data.to_vec())?;
 
     // gcode format
@@ -243,31 +523,36 @@ pub fn extract_previews_ascii(data: &[u8]) -> Result<Vec<Picture>> {
     // ; thumbnail end
     //
     // the encoded image can be a 'png', 'jpeg' or 'qoi'
+    // (Prusa uses 'thumbnail_JPG' and 'thumbnail_QOI' for the latter two)
 
-    let mut base64_images = vec![];
+    let mut previews = vec![];
     let mut base64_image = String::new();
 
-    let mut in_thumbnail_section = false;
+    let mut section_size = None;
 
     for (i, line) in content.lines().enumerate() {
         let trimmed_line = line.trim();
 
-        if in_thumbnail_section && (trimmed_line.starts_with("; thumbnail end") || !trimmed_line.starts_with(';')) {
-            in_thumbnail_section = false;
-
-            if !base64_image.is_empty() {
-                base64_images.push(base64_image);
-                base64_image = String::new();
+        if let Some((width, height)) = section_size {
+            if trimmed_line.starts_with("; thumbnail") || !trimmed_line.starts_with(';') {
+                section_size = None;
+
+                if !base64_image.is_empty() {
+                    previews.push(EncodedPreview {
+                        width,
+                        height,
+                        data: STANDARD.decode(&base64_image)?,
+                    });
+                    base64_image.clear();
+                }
+            } else {
+                let (_, base64) = trimmed_line.split_at(1);
+                base64_image.push_str(base64.trim());
             }
         }
 
-        if in_thumbnail_section && trimmed_line.starts_with(';') {
-            let (_, base64) = trimmed_line.split_at(1);
-            base64_image.push_str(base64.trim());
-        }
-
-        if trimmed_line.starts_with("; thumbnail begin") {
-            in_thumbnail_section = true;
+        if let Some(size) = parse_thumbnail_begin(trimmed_line) {
+            section_size = Some(size);
         }
 
         // gcode files can be huge we thus avoid scanning the whole file
@@ -277,21 +562,16 @@ pub fn extract_previews_ascii(data: &[u8]) -> Result<Vec<Picture>> {
         }
     }
 
-    let mut pcitures = vec![];
-
-    for base64_image in base64_images {
-        let image_bytes = STANDARD.decode(base64_image)?;
-
-        // try to decode the image (possible formats are 'png', 'jpeg', 'qoi')
-        if let Ok(image) = image::load_from_memory(&image_bytes) {
-            pcitures.push(Picture::from_img_buffer(image.to_rgba8()));
-        }
-    }
+    Ok(previews)
+}
 
-    // sort by size (ascending order)
-    pcitures.sort_by_key(|a| a.width() * a.height());
+/// Parses '; thumbnail begin <width>x<height> <length>' and its '_JPG'/'_QOI' variants
+fn parse_thumbnail_begin(line: &str) -> Option<(u32, u32)> {
+    let rest = line.strip_prefix("; thumbnail")?;
+    let (_, rest) = rest.split_once(" begin ")?;
+    let (width, height) = rest.split_whitespace().next()?.split_once('x')?;
 
-    Ok(pcitures)
+    Some((width.parse().ok()?, height.parse().ok()?))
 }
 
 #[cfg(test)]
@@ -319,6 +599,110 @@ mod test {
         assert_eq!(images[1].width(), 400);
     }
 
+    #[test]
+    fn test_metadata_ascii() {
+        let metadata = extract_metadata_ascii(GCODE_ASCII).unwrap();
+
+        assert_eq!(metadata.producer.as_deref(), Some("PrusaSlicer 2.7.1"));
+        assert_eq!(metadata.printer_model.as_deref(), Some("MK3S"));
+        assert_eq!(metadata.filament_type.as_deref(), Some("PLA"));
+        assert_eq!(metadata.estimated_printing_time, Some(38 * 60 + 21));
+        assert_eq!(metadata.filament_used_g, Some(6.54));
+        assert_eq!(metadata.layer_height, Some(0.2));
+    }
+
+    #[test]
+    fn test_metadata_binary() {
+        let metadata = extract_metadata_binary(GCODE_BIN).unwrap();
+
+        assert_eq!(metadata.producer.as_deref(), Some("PrusaSlicer 2.7.1"));
+        assert_eq!(metadata.printer_model.as_deref(), Some("MK3S"));
+        assert_eq!(metadata.estimated_printing_time, Some(38 * 60 + 21));
+        assert_eq!(metadata.filament_used_g, Some(6.54));
+        assert_eq!(metadata.layer_height, Some(0.2));
+    }
+
+    #[test]
+    fn test_metadata_summary() {
+        let metadata = extract_metadata_binary(GCODE_BIN).unwrap();
+        assert_eq!(metadata.summary(), "38m 6.5g 0.2mm");
+
+        let metadata = Metadata {
+            estimated_printing_time: Some(3 * 3600 + 5 * 60 + 7),
+            ..Default::default()
+        };
+        assert_eq!(metadata.summary(), "3h5m");
+
+        assert_eq!(Metadata::default().summary(), "");
+    }
+
+    #[test]
+    fn test_metadata_bambu_header() {
+        let data = b"; HEADER_BLOCK_START\n; model printing time: 36m 10s; total estimated time: 38m 21s\n; total filament weight [g] : 6.54\n";
+        let metadata = extract_metadata_ascii(data).unwrap();
+
+        assert_eq!(metadata.estimated_printing_time, Some(38 * 60 + 21));
+        assert_eq!(metadata.filament_used_g, Some(6.54));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("38m 21s"), Some(2301));
+        assert_eq!(parse_duration("1d 2h 3m 4s"), Some(93784));
+        assert_eq!(parse_duration("1234"), Some(1234));
+        assert_eq!(parse_duration("soon"), None);
+    }
+
+    #[test]
+    fn test_list_previews() {
+        for data in [GCODE_ASCII, GCODE_BIN] {
+            let previews = list_previews_from_data(data).unwrap();
+
+            assert_eq!(
+                previews,
+                vec![
+                    PreviewInfo {
+                        width: 32,
+                        height: 32,
+                        format: "png".to_string()
+                    },
+                    PreviewInfo {
+                        width: 400,
+                        height: 300,
+                        format: "png".to_string()
+                    },
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_select_preview() {
+        let previews = || extract_previews_binary(GCODE_BIN).unwrap();
+
+        assert_eq!(select_preview(previews(), 16, 16).unwrap().width(), 32);
+        assert_eq!(select_preview(previews(), 128, 128).unwrap().width(), 400);
+        assert_eq!(select_preview(previews(), 1024, 1024).unwrap().width(), 400);
+        assert!(select_preview(vec![], 128, 128).is_none());
+    }
+
+    #[test]
+    fn test_deflate_block() {
+        // libbgcode writes zlib streams, a raw deflate decoder rejects their header
+        let metadata = b"printer_model=MK4\nestimated printing time (normal mode)=1h 2m 3s\n";
+        let mut encoder = flate2::write::ZlibEncoder::new(vec![], flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, metadata).unwrap();
+
+        let block = Block {
+            kind: BlockType::PrintMetadata,
+            compression: CompressionType::Deflate,
+            params: vec![0, 0],
+            data: encoder.finish().unwrap(),
+        };
+
+        assert_eq!(block.uncompressed_data().unwrap(), metadata);
+    }
+
     #[test]
     fn test_detect_format() {
         assert_eq!(detect_format(GCODE_BIN).unwrap(), GCodeType::Binary);
@@ -0,0 +1,237 @@
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Result};
+use base64::{engine::general_purpose, Engine};
+use gltf::{buffer::Source, mesh::Mode, Gltf, Node};
+
+use crate::stl::mesh::{transform_point, Facet, Mat4, Mesh, Triangle, Vec3};
+
+pub fn read_mesh_from_file<P: AsRef<Path>>(filename: P, material_colors: bool) -> Result<Mesh> {
+    let data = std::fs::read(&filename)?;
+
+    // external buffers are relative to the gltf file
+    read_mesh_from_data(&data, filename.as_ref().parent(), material_colors)
+}
+
+/// Reads the triangles of the default scene of a glTF or GLB file
+///
+/// The model is converted from glTF's Y-up to Z-up.
+/// If `material_colors` is set, the base color of the primitives' material is used as model color.
+pub fn read_mesh_from_data(data: &[u8], base_dir: Option<&Path>, material_colors: bool) -> Result<Mesh> {
+    let gltf = Gltf::from_slice(data)?;
+
+    let buffers = gltf
+        .buffers()
+        .map(|buffer| match buffer.source() {
+            Source::Bin => match &gltf.blob {
+                Some(blob) => Ok(blob.clone()),
+                None => bail!("missing binary chunk"),
+            },
+            Source::Uri(uri) => read_uri(uri, base_dir),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let Some(scene) = gltf.default_scene().or_else(|| gltf.scenes().next()) else {
+        bail!("gltf contains no scene")
+    };
+
+    // Y-up to Z-up
+    let transform = glm::rotation(90.0f32.to_radians(), &Vec3::new(1.0, 0.0, 0.0));
+
+    let mut triangles = Mesh::default();
+    for node in scene.nodes() {
+        read_node(&node, &transform, &buffers, material_colors, &mut triangles)?;
+    }
+
+    Ok(triangles)
+}
+
+fn read_uri(uri: &str, base_dir: Option<&Path>) -> Result<Vec<u8>> {
+    // embedded buffer, e.g. 'data:application/octet-stream;base64,...'
+    if let Some(data) = uri.strip_prefix("data:") {
+        let Some((_, data)) = data.split_once(";base64,") else {
+            bail!("unsupported data uri")
+        };
+        return Ok(general_purpose::STANDARD.decode(data)?);
+    }
+
+    let Some(base_dir) = base_dir else {
+        bail!("cannot resolve external buffer '{uri}'")
+    };
+
+    Ok(std::fs::read(base_dir.join(relative_path(uri)?))?)
+}
+
+/// The path of an external buffer, which must stay within the directory of the gltf file
+fn relative_path(uri: &str) -> Result<PathBuf> {
+    // a relative reference has no scheme, e.g. 'file:' or 'http:'
+    if uri.split('/').next().is_some_and(|segment| segment.contains(':')) {
+        bail!("unsupported uri '{uri}'");
+    }
+
+    let path = PathBuf::from(percent_decode(uri)?);
+    if !path
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        bail!("external buffer '{uri}' is outside of the model directory");
+    }
+
+    Ok(path)
+}
+
+fn percent_decode(uri: &str) -> Result<String> {
+    let mut bytes = vec![];
+    let mut rest = uri.as_bytes();
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte != b'%' {
+            bytes.push(byte);
+            rest = tail;
+            continue;
+        }
+
+        let Some(hex) = tail.get(..2).filter(|hex| hex.iter().all(u8::is_ascii_hexdigit)) else {
+            bail!("invalid uri '{uri}'")
+        };
+        // two hex digits always fit
+        bytes.push(u8::from_str_radix(std::str::from_utf8(hex)?, 16)?);
+        rest = &tail[2..];
+    }
+
+    Ok(String::from_utf8(bytes)?)
+}
+
+fn read_node(
+    node: &Node,
+    parent_transform: &Mat4,
+    buffers: &[Vec<u8>],
+    material_colors: bool,
+    triangles: &mut Mesh,
+) -> Result<()> {
+    let local_transform: Mat4 = node.transform().matrix().into();
+    let transform = parent_transform * local_transform;
+    let normal_transform = glm::transpose(&glm::inverse(&transform));
+
+    if let Some(mesh) = node.mesh() {
+        for primitive in mesh.primitives() {
+            if primitive.mode() != Mode::Triangles {
+                continue;
+            }
+
+            let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|b| b.as_slice()));
+
+            let Some(positions) = reader.read_positions() else {
+                continue;
+            };
+            let positions: Vec<Vec3> = positions.map(|p| transform_point(&transform, &p.into())).collect();
+
+            let normals: Option<Vec<Vec3>> = reader
+                .read_normals()
+                .map(|normals| {
+                    normals
+                        .map(|n| (normal_transform * glm::vec3_to_vec4(&n.into())).xyz())
+                        .collect::<Vec<_>>()
+                })
+                .filter(|normals| normals.len() == positions.len());
+
+            let indices: Vec<usize> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+                None => (0..positions.len()).collect(),
+            };
+
+            let color = if material_colors && primitive.material().index().is_some() {
+                let [r, g, b, _] = primitive.material().pbr_metallic_roughness().base_color_factor();
+                Some(Vec3::new(r, g, b))
+            } else {
+                None
+            };
+
+            for corners in indices.chunks_exact(3) {
+                if corners.iter().any(|&i| i >= positions.len()) {
+                    bail!("vertex index out of range");
+                }
+
+                let vertices = [positions[corners[0]], positions[corners[1]], positions[corners[2]]];
+
+                // flat shading: average the vertex normals if there are any
+                let normal = match &normals {
+                    Some(normals) => corners.iter().map(|&i| normals[i]).sum::<Vec3>(),
+                    None => (vertices[1] - vertices[0]).cross(&(vertices[2] - vertices[0])),
+                }
+                .normalize();
+
+                let mut facet = Facet::from(Triangle::new(vertices, normal));
+                if let Some(color) = color {
+                    facet = facet.with_colors([color; 3]);
+                }
+
+                triangles.push(facet);
+            }
+        }
+    }
+
+    for child in node.children() {
+        read_node(&child, &transform, buffers, material_colors, triangles)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const QUAD_GLB: &[u8] = include_bytes!("../test_models/quad.glb");
+    const QUAD_GLTF: &[u8] = include_bytes!("../test_models/quad.gltf");
+
+    fn assert_vec3_eq(a: Vec3, b: Vec3) {
+        assert!((a - b).norm() < 1e-5, "{a} != {b}");
+    }
+
+    #[test]
+    fn read_glb_test() {
+        let mesh = read_mesh_from_data(QUAD_GLB, None, true).unwrap();
+
+        assert_eq!(mesh.len(), 2);
+
+        // node hierarchy: translation (0, 2, 0) -> scale 2
+        assert_vec3_eq(mesh[0].vertices[0], Vec3::new(0.0, 0.0, 2.0));
+        assert_vec3_eq(mesh[0].vertices[2], Vec3::new(2.0, 2.0, 2.0));
+        assert_vec3_eq(mesh[1].vertices[2], Vec3::new(0.0, 2.0, 2.0));
+        assert_vec3_eq(mesh[0].normal, Vec3::new(0.0, 0.0, 1.0));
+
+        assert_eq!(mesh.facet(0).colors, Some([Vec3::new(1.0, 0.0, 0.0); 3]));
+    }
+
+    #[test]
+    fn read_gltf_test() {
+        let mesh = read_mesh_from_data(QUAD_GLTF, None, false).unwrap();
+
+        assert_eq!(mesh.len(), 2);
+        assert_vec3_eq(mesh[1].vertices[1], Vec3::new(2.0, 2.0, 2.0));
+        assert_eq!(mesh.facet(0).colors, None);
+    }
+
+    #[test]
+    fn relative_path_test() {
+        assert_eq!(relative_path("quad.bin").unwrap(), PathBuf::from("quad.bin"));
+        assert_eq!(
+            relative_path("buffers/quad.bin").unwrap(),
+            PathBuf::from("buffers/quad.bin")
+        );
+        assert_eq!(
+            relative_path("my%20buffer.bin").unwrap(),
+            PathBuf::from("my buffer.bin")
+        );
+
+        assert!(relative_path("../quad.bin").is_err());
+        assert!(relative_path("buffers/../../quad.bin").is_err());
+        assert!(relative_path("%2e%2e/quad.bin").is_err());
+        assert!(relative_path("/etc/passwd").is_err());
+        assert!(relative_path("%2Fetc/passwd").is_err());
+        assert!(relative_path("file:///etc/passwd").is_err());
+        assert!(relative_path("https://example.com/quad.bin").is_err());
+        assert!(relative_path("quad%2.bin").is_err());
+    }
+}
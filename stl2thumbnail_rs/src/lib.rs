@@ -1,11 +1,18 @@
 use picture::Color;
 use std::time::Duration;
 
+pub mod amf;
 pub mod ffi;
 pub mod gcode;
+pub mod gltf;
+pub mod off;
 pub mod picture;
+pub mod ply;
+pub mod resin;
 pub mod stl;
 pub mod threemf;
+pub mod ufp;
+pub mod x3d;
 
 pub struct Settings {
     pub verbose: bool,
@@ -18,4 +25,17 @@ pub struct Settings {
     pub cam_azimuth: f32,
     pub timeout: Option<Duration>,
     pub background_color: Color,
+    pub color_solids: bool,
+    pub tolerant: bool,
+    /// makes the triangle orientation consistent (not available in lazy mode)
+    pub repair_normals: bool,
+    /// renders back faces
+    pub two_sided: bool,
+    pub orientation: stl::orientation::Orientation,
+    /// simplifies meshes with more triangles before rendering
+    pub decimate_above: Option<usize>,
+    /// opacity of the shadow on the build plate
+    pub shadow: Option<f32>,
+    /// adds the estimated weight to the size hint
+    pub material: Option<stl::analysis::MaterialSettings>,
 }
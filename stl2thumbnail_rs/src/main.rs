@@ -1,18 +1,58 @@
 use stl2thumbnail::*;
 
 use anyhow::{bail, Result};
-use stl::mesh::LazyMesh;
-use stl::parser::Parser;
+use serde::Serialize;
+use stl::analysis::{self, MaterialEstimate, MaterialSettings, MeshStats};
+use stl::mesh::{LazyMesh, Mesh};
+use stl::orientation::Orientation;
+use stl::parser::{is_compressed, ParseMode, Parser};
+use stl::RenderSummary;
 
 use clap::{builder::PathBufValueParser, Arg, ArgAction, ArgMatches, Command};
 use std::{
-    path::PathBuf,
+    fs,
+    path::{Path, PathBuf},
     time::{Duration, Instant},
 };
 
 fn main() -> Result<()> {
-    let stl_command = Command::new("stl")
-        .about("Renders an image of an stl file")
+    let matches = cli().get_matches();
+
+    if let Some((subcommand, matches)) = matches.subcommand() {
+        match subcommand {
+            "stl" => command_stl(matches)?,
+            "gcode" => command_gcode(matches)?,
+            "3mf" => command_3mf(matches)?,
+            "ufp" => command_ufp(matches)?,
+            "resin" => command_resin(matches)?,
+            "info" => command_info(matches)?,
+            "stats" => command_stats(matches)?,
+            _ => unimplemented!(),
+        }
+    }
+
+    Ok(())
+}
+
+fn cli() -> Command {
+    Command::new("stl2thumbnail")
+        .version(clap::crate_version!())
+        .arg_required_else_help(true)
+        .disable_help_flag(true)
+        .about("STL thumbnail generator")
+        .subcommand(stl_command())
+        .subcommand(gcode_command())
+        .subcommand(threemf_command())
+        .subcommand(ufp_command())
+        .subcommand(resin_command())
+        .subcommand(info_command())
+        .subcommand(stats_command())
+}
+
+/// The `stl` subcommand
+fn stl_command() -> Command {
+    Command::new("stl")
+        .about("Renders an image of a mesh (stl, ply, gltf, amf, off, wrl, x3d)")
         .arg(
             Arg::new("INPUT")
                 .index(1)
@@ -46,7 +86,7 @@ fn main() -> Result<()> {
                 .short('l')
                 .long("lazy")
                 .action(ArgAction::SetTrue)
-                .help("Enables low memory usage mode"),
+                .help("Enables low memory usage mode (huge meshes are not simplified, timeouts save a partial image)"),
         )
         .arg(
             Arg::new("RECALC_NORMALS")
@@ -80,6 +120,71 @@ fn main() -> Result<()> {
                 .action(ArgAction::SetTrue)
                 .help("Draws the dimensions underneath the model (requires height of at least 256 pixels)"),
         )
+        .arg(
+            Arg::new("COLOR_SOLIDS")
+                .long("color-solids")
+                .action(ArgAction::SetTrue)
+                .help("Renders each solid of an ascii stl file in a different color"),
+        )
+        .arg(
+            Arg::new("TOLERANT")
+                .long("tolerant")
+                .action(ArgAction::SetTrue)
+                .help("Recovers what it can from malformed stl files and reports the skipped data"),
+        )
+        .arg(
+            Arg::new("REPAIR_NORMALS")
+                .long("repair-normals")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("LAZY")
+                .help("Orients flipped triangles consistently and recalculates the normals"),
+        )
+        .arg(
+            Arg::new("TWO_SIDED")
+                .long("two-sided")
+                .action(ArgAction::SetTrue)
+                .help("Renders back faces, e.g. of models with broken normals"),
+        )
+        .arg(
+            Arg::new("SHADOW")
+                .long("shadow")
+                .action(ArgAction::Set)
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("0.4")
+                .value_parser(clap::value_parser!(f32))
+                .help(
+                    "Casts a shadow onto the build plate, optionally with an opacity (--shadow=0.0-1.0, default: 0.4)",
+                ),
+        )
+        .arg(
+            Arg::new("DECIMATE_ABOVE")
+                .long("decimate-above")
+                .action(ArgAction::Set)
+                .value_parser(clap::value_parser!(usize))
+                .help(
+                    "Simplifies meshes with more triangles to about a pixel's resolution \
+                 (default: 1000000 for turntables, 10000000 for stills; 0: never)",
+                ),
+        )
+        .arg(
+            Arg::new("ORIENTATION")
+                .long("orientation")
+                .action(ArgAction::Set)
+                .value_parser(["z-up", "y-up", "auto"])
+                .default_value("z-up")
+                .help(
+                    "Up axis of the model, 'auto' lays it on its most stable face \
+                 (gltf, glb, x3d and wrl files are already converted to z-up)",
+                ),
+        )
+        .args(material_args())
+        .arg(
+            Arg::new("MATERIAL_COLORS")
+                .long("material-colors")
+                .action(ArgAction::SetTrue)
+                .help("Uses the base color of the materials as model color (gltf, glb)"),
+        )
         .arg(
             Arg::new("CAM_ELEVATION")
                 .long("cam-elevation")
@@ -115,16 +220,77 @@ fn main() -> Result<()> {
             Arg::new("TIMEOUT")
                 .long("timeout")
                 .action(ArgAction::Set)
-                .help("Sets the time budget for the rendering process"),
+                .value_parser(clap::value_parser!(u64))
+                .help("Sets the time budget for the rendering process in ms, a coarse preview is saved if it expires"),
         )
         .arg(
             Arg::new("HELP")
                 .long("help")
                 .action(ArgAction::HelpLong)
                 .help("Prints this"),
-        );
+        )
+}
+
+/// The `gcode` subcommand
+fn gcode_command() -> Command {
+    Command::new("gcode")
+        .about("Extracts a thumbnail embedded in a gcode file (gcode, bgcode or a sliced 3mf)")
+        .arg(
+            Arg::new("INPUT")
+                .index(1)
+                .help("Input filename")
+                .required(true)
+                .value_parser(PathBufValueParser::new()),
+        )
+        .arg(
+            Arg::new("OUTPUT")
+                .index(2)
+                .help("Output filename")
+                .required_unless_present("LIST")
+                .value_parser(PathBufValueParser::new()),
+        )
+        .arg(
+            Arg::new("WIDTH")
+                .short('w')
+                .long("width")
+                .action(ArgAction::Set)
+                .default_value("256")
+                .value_parser(clap::value_parser!(u32))
+                .help("Width of the generated image"),
+        )
+        .arg(
+            Arg::new("HEIGHT")
+                .short('h')
+                .long("height")
+                .action(ArgAction::Set)
+                .default_value("256")
+                .value_parser(clap::value_parser!(u32))
+                .help("Height of the generated image"),
+        )
+        .arg(
+            Arg::new("PRINT_INFO")
+                .short('i')
+                .long("info")
+                .action(ArgAction::SetTrue)
+                .help("Draws the print time, filament weight and layer height underneath the preview"),
+        )
+        .arg(
+            Arg::new("LIST")
+                .long("list")
+                .action(ArgAction::SetTrue)
+                .help("Lists the embedded previews instead of extracting one"),
+        )
+        .arg(
+            Arg::new("HELP")
+                .long("help")
+                .action(ArgAction::HelpLong)
+                .help("Prints this"),
+        )
+}
 
-    let gcode_command = Command::new("gcode")
+/// The `3mf` subcommand
+fn threemf_command() -> Command {
+    Command::new("3mf")
         .about("Extracts a thumbnail embedded in a gcode file")
         .arg(
             Arg::new("INPUT")
@@ -158,15 +324,43 @@ fn main() -> Result<()> {
                 .value_parser(clap::value_parser!(u32))
                 .help("Height of the generated image"),
         )
+        .arg(
+            Arg::new("PLATE")
+                .short('p')
+                .long("plate")
+                .action(ArgAction::Set)
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .help("Extracts the thumbnail of the given build plate (Bambu, Orca)"),
+        )
+        .arg(
+            Arg::new("ALL_PLATES")
+                .short('a')
+                .long("all-plates")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("PLATE")
+                .help("Arranges the thumbnails of all build plates on a grid (Bambu, Orca)"),
+        )
+        .arg(
+            Arg::new("PRINT_INFO")
+                .short('i')
+                .long("info")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Draws the print time, filament weight and layer height of sliced projects underneath the preview",
+                ),
+        )
         .arg(
             Arg::new("HELP")
                 .long("help")
                 .action(ArgAction::HelpLong)
                 .help("Prints this"),
-        );
+        )
+}
 
-    let threemf_command = Command::new("3mf")
-        .about("Extracts a thumbnail embedded in a gcode file")
+/// The `ufp` subcommand
+fn ufp_command() -> Command {
+    Command::new("ufp")
+        .about("Extracts a thumbnail embedded in an Ultimaker Format Package")
         .arg(
             Arg::new("INPUT")
                 .index(1)
@@ -204,36 +398,96 @@ fn main() -> Result<()> {
                 .long("help")
                 .action(ArgAction::HelpLong)
                 .help("Prints this"),
-        );
+        )
+}
 
-    let matches = Command::new("stl2thumbnail")
-        .version(clap::crate_version!())
-        .arg_required_else_help(true)
-        .disable_help_flag(true)
-        .about("STL thumbnail generator")
-        .subcommand(stl_command)
-        .subcommand(gcode_command)
-        .subcommand(threemf_command)
-        .get_matches();
+/// The `resin` subcommand
+fn resin_command() -> Command {
+    Command::new("resin")
+        .about("Extracts a thumbnail embedded in a resin printer slice file (sl1, ctb, cbddlp, pwmx, goo)")
+        .arg(
+            Arg::new("INPUT")
+                .index(1)
+                .help("Input filename")
+                .required(true)
+                .value_parser(PathBufValueParser::new()),
+        )
+        .arg(
+            Arg::new("OUTPUT")
+                .index(2)
+                .help("Output filename")
+                .required(true)
+                .value_parser(PathBufValueParser::new()),
+        )
+        .arg(
+            Arg::new("WIDTH")
+                .short('w')
+                .long("width")
+                .action(ArgAction::Set)
+                .default_value("256")
+                .value_parser(clap::value_parser!(u32))
+                .help("Width of the generated image"),
+        )
+        .arg(
+            Arg::new("HEIGHT")
+                .short('h')
+                .long("height")
+                .action(ArgAction::Set)
+                .default_value("256")
+                .value_parser(clap::value_parser!(u32))
+                .help("Height of the generated image"),
+        )
+        .arg(
+            Arg::new("HELP")
+                .long("help")
+                .action(ArgAction::HelpLong)
+                .help("Prints this"),
+        )
+}
 
-    if let Some((subcommand, matches)) = matches.subcommand() {
-        match subcommand {
-            "stl" => command_stl(matches)?,
-            "gcode" => command_gcode(matches)?,
-            "3mf" => command_3mf(matches)?,
-            _ => unimplemented!(),
-        }
-    }
+/// The `info` subcommand
+fn info_command() -> Command {
+    Command::new("info")
+        .about("Prints the print information of a gcode file or a sliced 3mf (.gcode.3mf) as JSON")
+        .arg(
+            Arg::new("INPUT")
+                .index(1)
+                .help("Input filename")
+                .required(true)
+                .value_parser(PathBufValueParser::new()),
+        )
+        .arg(
+            Arg::new("HELP")
+                .long("help")
+                .action(ArgAction::HelpLong)
+                .help("Prints this"),
+        )
+}
 
-    Ok(())
+/// The `stats` subcommand
+fn stats_command() -> Command {
+    Command::new("stats")
+        .about("Prints the volume, surface area, dimensions and defects of a mesh as JSON")
+        .arg(
+            Arg::new("INPUT")
+                .index(1)
+                .help("Input filename")
+                .required(true)
+                .value_parser(PathBufValueParser::new()),
+        )
+        .args(material_args())
+        .arg(
+            Arg::new("HELP")
+                .long("help")
+                .action(ArgAction::HelpLong)
+                .help("Prints this"),
+        )
 }
 
 fn command_stl(matches: &ArgMatches) -> Result<()> {
     let input = matches.get_one::<PathBuf>("INPUT").unwrap();
     let output = matches.get_one::<PathBuf>("OUTPUT").unwrap();
 
-    let file_extension = input.extension().map(|ex| ex.to_ascii_lowercase());
-
     let width = matches.get_one::<u32>("WIDTH").unwrap();
     let height = matches.get_one::<u32>("HEIGHT").unwrap();
 
@@ -252,6 +506,24 @@ fn command_stl(matches: &ArgMatches) -> Result<()> {
             .unwrap_or(&"FFFFFFFF".to_string())
             .as_str()
             .try_into()?,
+        color_solids: *matches.get_one::<bool>("COLOR_SOLIDS").unwrap(),
+        tolerant: *matches.get_one::<bool>("TOLERANT").unwrap(),
+        repair_normals: *matches.get_one::<bool>("REPAIR_NORMALS").unwrap(),
+        two_sided: *matches.get_one::<bool>("TWO_SIDED").unwrap(),
+        shadow: matches.get_one::<f32>("SHADOW").map(|opacity| opacity.clamp(0.0, 1.0)),
+        orientation: match matches.get_one::<String>("ORIENTATION").unwrap().as_str() {
+            "y-up" => Orientation::YUp,
+            "auto" => Orientation::Auto,
+            _ => Orientation::ZUp,
+        },
+        // decimating costs several frames (benches/decimate.rs), stills only simplify huge meshes
+        decimate_above: match matches.get_one::<usize>("DECIMATE_ABOVE") {
+            Some(0) => None,
+            Some(&count) => Some(count),
+            None if *matches.get_one::<bool>("TURNTABLE").unwrap() => Some(1_000_000),
+            None => Some(10_000_000),
+        },
+        material: material_settings(matches),
     };
 
     if settings.verbose {
@@ -265,54 +537,178 @@ fn command_stl(matches: &ArgMatches) -> Result<()> {
         println!("Cam elevation         {}°", settings.cam_elevation);
         println!("Cam azimuth           {}°", settings.cam_azimuth);
         println!("Timeout               {:?}", settings.timeout);
+        println!("Color solids          '{}'", settings.color_solids);
+        println!("Tolerant parsing      '{}'", settings.tolerant);
+        println!("Repair normals        '{}'", settings.repair_normals);
+        println!("Two-sided             '{}'", settings.two_sided);
+        println!("Shadow                {:?}", settings.shadow);
+        println!("Orientation           {:?}", settings.orientation);
+        println!("Decimate above        {:?}", settings.decimate_above);
+        println!("Material              {:?}", settings.material);
     }
 
-    if file_extension == Some("stl".into()) {
-        let start_time = Instant::now();
-        let mut parser = Parser::from_file(input, settings.recalculate_normals)?;
+    let start_time = Instant::now();
 
-        if settings.lazy {
-            let parsed_mesh = LazyMesh::new(&mut parser);
-            stl::render_stl(*width, *height, &parsed_mesh, output, &settings)?;
+    let rendering = if is_stl(input) {
+        let mode = if settings.tolerant {
+            ParseMode::Tolerant
         } else {
-            let parsed_mesh = parser.read_all()?;
-            stl::render_stl(*width, *height, &parsed_mesh, output, &settings)?;
-        }
+            ParseMode::Strict
+        };
+        let (rendering, triangles_read, warnings) = if settings.lazy {
+            // compressed files would be decompressed into memory for every pass
+            if is_compressed(&mut fs::File::open(input)?)? {
+                bail!("Low memory usage mode does not support compressed files");
+            }
 
-        if settings.verbose {
-            println!(
-                "Saved as '{}' (took {}s)",
-                output.to_string_lossy(),
-                Instant::now().duration_since(start_time).as_secs_f32()
-            );
+            // the file is reopened for every pass
+            let parsed_mesh =
+                LazyMesh::try_new(|| Parser::from_file_with_mode(input, settings.recalculate_normals, mode))?;
+            let rendering = stl::render_stl(*width, *height, &parsed_mesh, &settings)?;
+            // nothing is saved if a pass failed to read the file
+            parsed_mesh.check()?;
+
+            (rendering, parsed_mesh.triangles_read(), parsed_mesh.warnings())
+        } else {
+            let mut parser = Parser::from_file_with_mode(input, settings.recalculate_normals, mode)?;
+            let mut parsed_mesh = parser.read_all()?;
+            if settings.repair_normals {
+                parsed_mesh.repair_orientation();
+            }
+            let rendering = stl::render_stl(*width, *height, &parsed_mesh, &settings)?;
+
+            (rendering, parser.triangles_read(), parser.warnings().to_vec())
+        };
+
+        if settings.tolerant {
+            for warning in warnings {
+                eprintln!("Warning: {warning}");
+            }
+            eprintln!("Read {triangles_read} triangles");
         }
+
+        rendering
     } else {
-        bail!("not a stl file");
+        let material_colors = *matches.get_one::<bool>("MATERIAL_COLORS").unwrap();
+        let mut parsed_mesh = read_mesh(input, material_colors)?;
+        if settings.repair_normals {
+            parsed_mesh.repair_orientation();
+        }
+        stl::render_stl(*width, *height, &parsed_mesh, &settings)?
+    };
+
+    rendering.save(output)?;
+
+    match rendering.summary {
+        RenderSummary { coarse_frames: 0, .. } => {}
+        RenderSummary { frames: 1, .. } => println!("... timeout, saved a coarse preview"),
+        RenderSummary { frames, coarse_frames } => {
+            println!("... timeout, {coarse_frames} of {frames} frames are coarse previews")
+        }
+    }
+
+    if settings.verbose {
+        println!(
+            "Saved as '{}' (took {}s)",
+            output.to_string_lossy(),
+            Instant::now().duration_since(start_time).as_secs_f32()
+        );
     }
 
     Ok(())
 }
 
+/// Reads a mesh in any of the supported formats
+fn read_mesh(input: &Path, material_colors: bool) -> Result<Mesh> {
+    let file_extension = input.extension().map(|ex| ex.to_ascii_lowercase());
+
+    if is_stl(input) {
+        Parser::from_file(input, false)?.read_all()
+    } else if file_extension == Some("ply".into()) {
+        ply::read_mesh_from_file(input)
+    } else if file_extension == Some("gltf".into()) || file_extension == Some("glb".into()) {
+        gltf::read_mesh_from_file(input, material_colors)
+    } else if file_extension == Some("amf".into()) {
+        amf::read_mesh_from_file(input)
+    } else if file_extension == Some("off".into()) {
+        off::read_mesh_from_file(input)
+    } else if matches!(
+        file_extension.as_ref().and_then(|ex| ex.to_str()),
+        Some("wrl" | "x3d" | "x3dv" | "x3dz")
+    ) {
+        x3d::read_mesh_from_file(input)
+    } else {
+        bail!("unsupported file type")
+    }
+}
+
+/// Plain and compressed stl files ('.stl', '.stl.gz', '.stl.zip', '.stl.xz')
+fn is_stl(input: &Path) -> bool {
+    let name = input.to_string_lossy().to_ascii_lowercase();
+    [".stl", ".stl.gz", ".stl.zip", ".stl.xz"]
+        .iter()
+        .any(|ext| name.ends_with(ext))
+}
+
 fn command_gcode(matches: &ArgMatches) -> Result<()> {
     let input = matches.get_one::<PathBuf>("INPUT").unwrap();
-    let output = matches.get_one::<PathBuf>("OUTPUT").unwrap();
-    let width = matches.get_one::<u32>("WIDTH").unwrap();
-    let height = matches.get_one::<u32>("HEIGHT").unwrap();
+    let width = *matches.get_one::<u32>("WIDTH").unwrap();
+    let height = *matches.get_one::<u32>("HEIGHT").unwrap();
+    let print_info = *matches.get_one::<bool>("PRINT_INFO").unwrap();
+    let list = *matches.get_one::<bool>("LIST").unwrap();
 
     let file_extension = input.extension().map(|ex| ex.to_ascii_lowercase());
 
-    if file_extension == Some("gcode".into()) || file_extension == Some("bgcode".into()) {
-        let mut previews = gcode::extract_previews_from_file(input)?;
-        if let Some(preview) = previews.last_mut() {
-            preview.resize_keep_aspect_ratio(*width, *height).save(output)?;
+    let (previews, metadata) = if threemf::is_print_package_from_file(input) {
+        if list {
+            print_preview_list(&threemf::list_print_previews_from_file(input)?);
+            return Ok(());
         }
+
+        let previews = threemf::extract_print_previews_from_file(input)?;
+        let metadata = print_info
+            .then(|| threemf::extract_print_metadata_from_file(input))
+            .transpose()?;
+
+        (previews, metadata)
+    } else if file_extension == Some("gcode".into()) || file_extension == Some("bgcode".into()) {
+        let data = std::fs::read(input)?;
+
+        if list {
+            print_preview_list(&gcode::list_previews_from_data(&data)?);
+            return Ok(());
+        }
+
+        let previews = gcode::extract_previews_from_data(&data)?;
+        let metadata = print_info
+            .then(|| gcode::extract_metadata_from_data(&data))
+            .transpose()?;
+
+        (previews, metadata)
     } else {
         bail!("not a gcode file");
+    };
+
+    let output = matches.get_one::<PathBuf>("OUTPUT").unwrap();
+    if let Some(mut preview) = gcode::select_preview(previews, width, height) {
+        preview.resize_keep_aspect_ratio(width, height);
+
+        if let Some(metadata) = metadata {
+            gcode::draw_metadata(&mut preview, &metadata);
+        }
+
+        preview.save(output)?;
     }
 
     Ok(())
 }
 
+fn print_preview_list(previews: &[gcode::PreviewInfo]) {
+    for preview in previews {
+        println!("{}x{} {}", preview.width, preview.height, preview.format);
+    }
+}
+
 fn command_3mf(matches: &ArgMatches) -> Result<()> {
     let input = matches.get_one::<PathBuf>("INPUT").unwrap();
     let output = matches.get_one::<PathBuf>("OUTPUT").unwrap();
@@ -322,11 +718,184 @@ fn command_3mf(matches: &ArgMatches) -> Result<()> {
     let file_extension = input.extension().map(|ex| ex.to_ascii_lowercase());
 
     if file_extension == Some("3mf".into()) {
-        let mut preview = threemf::extract_preview_from_file(input)?;
-        preview.resize_keep_aspect_ratio(width, height).save(output)?;
+        let mut preview = if let Some(plate) = matches.get_one::<u32>("PLATE") {
+            threemf::extract_plate_preview_from_file(input, *plate)?
+        } else if *matches.get_one::<bool>("ALL_PLATES").unwrap() {
+            threemf::extract_plates_grid_from_file(input)?
+        } else {
+            threemf::extract_preview_from_file(input)?
+        };
+        preview.resize_keep_aspect_ratio(width, height);
+
+        if *matches.get_one::<bool>("PRINT_INFO").unwrap() {
+            let metadata = threemf::extract_print_metadata_from_file(input)?;
+            gcode::draw_metadata(&mut preview, &metadata);
+        }
+
+        preview.save(output)?;
     } else {
         bail!("not a 3mf file");
     }
 
     Ok(())
 }
+
+fn command_ufp(matches: &ArgMatches) -> Result<()> {
+    let input = matches.get_one::<PathBuf>("INPUT").unwrap();
+    let output = matches.get_one::<PathBuf>("OUTPUT").unwrap();
+    let width = *matches.get_one::<u32>("WIDTH").unwrap();
+    let height = *matches.get_one::<u32>("HEIGHT").unwrap();
+
+    let file_extension = input.extension().map(|ex| ex.to_ascii_lowercase());
+
+    if file_extension == Some("ufp".into()) {
+        let mut preview = ufp::extract_preview_from_file(input)?;
+        preview.resize_keep_aspect_ratio(width, height).save(output)?;
+    } else {
+        bail!("not a ufp file");
+    }
+
+    Ok(())
+}
+
+fn command_resin(matches: &ArgMatches) -> Result<()> {
+    let input = matches.get_one::<PathBuf>("INPUT").unwrap();
+    let output = matches.get_one::<PathBuf>("OUTPUT").unwrap();
+    let width = *matches.get_one::<u32>("WIDTH").unwrap();
+    let height = *matches.get_one::<u32>("HEIGHT").unwrap();
+
+    let is_resin = input
+        .extension()
+        .and_then(|ex| ex.to_str())
+        .is_some_and(|ex| resin::EXTENSIONS.contains(&ex.to_ascii_lowercase().as_str()));
+    if !is_resin {
+        bail!("not a resin file");
+    }
+
+    let previews = resin::extract_previews_from_file(input)?;
+    let Some(mut preview) = gcode::select_preview(previews, width, height) else {
+        bail!("no preview in resin file");
+    };
+    preview.resize_keep_aspect_ratio(width, height).save(output)?;
+
+    Ok(())
+}
+
+fn command_info(matches: &ArgMatches) -> Result<()> {
+    let input = matches.get_one::<PathBuf>("INPUT").unwrap();
+
+    let file_extension = input.extension().map(|ex| ex.to_ascii_lowercase());
+
+    if threemf::is_print_package_from_file(input) {
+        let metadata = threemf::extract_print_metadata_from_file(input)?;
+        println!("{}", serde_json::to_string_pretty(&metadata)?);
+    } else if file_extension == Some("gcode".into()) || file_extension == Some("bgcode".into()) {
+        let metadata = gcode::extract_metadata_from_file(input)?;
+        println!("{}", serde_json::to_string_pretty(&metadata)?);
+    } else {
+        bail!("not a gcode file");
+    }
+
+    Ok(())
+}
+
+fn command_stats(matches: &ArgMatches) -> Result<()> {
+    let input = matches.get_one::<PathBuf>("INPUT").unwrap();
+
+    #[derive(Serialize)]
+    struct Stats {
+        #[serde(flatten)]
+        stats: MeshStats,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        material: Option<MaterialEstimate>,
+    }
+
+    let mesh = read_mesh(input, false)?;
+    let stats = analysis::analyze(&mesh);
+    let material = material_settings(matches)
+        .map(|settings| analysis::estimate_material(stats.volume, stats.surface_area, &settings));
+
+    println!("{}", serde_json::to_string_pretty(&Stats { stats, material })?);
+
+    Ok(())
+}
+
+/// Print parameters of the material estimate, enabled by '--density'
+fn material_args() -> [Arg; 5] {
+    [
+        Arg::new("DENSITY")
+            .long("density")
+            .action(ArgAction::Set)
+            .value_parser(clap::value_parser!(f64))
+            .help("Estimates the printed weight for a material density in g/cm³ (PLA: 1.24)"),
+        Arg::new("INFILL")
+            .long("infill")
+            .action(ArgAction::Set)
+            .default_value("20")
+            .value_parser(clap::value_parser!(f64))
+            .help("Infill percentage of the estimate (use 100 for resin prints)"),
+        Arg::new("WALLS")
+            .long("walls")
+            .action(ArgAction::Set)
+            .default_value("2")
+            .value_parser(clap::value_parser!(u32))
+            .help("Wall count of the estimate"),
+        Arg::new("NOZZLE")
+            .long("nozzle")
+            .action(ArgAction::Set)
+            .default_value("0.4")
+            .value_parser(clap::value_parser!(f64))
+            .help("Nozzle width of the estimate in mm"),
+        Arg::new("COST")
+            .long("cost")
+            .action(ArgAction::Set)
+            .value_parser(clap::value_parser!(f64))
+            .help("Material cost per kg of the estimate"),
+    ]
+}
+
+fn material_settings(matches: &ArgMatches) -> Option<MaterialSettings> {
+    Some(MaterialSettings {
+        density: *matches.get_one::<f64>("DENSITY")?,
+        infill: *matches.get_one::<f64>("INFILL").unwrap() / 100.0,
+        wall_count: *matches.get_one::<u32>("WALLS").unwrap(),
+        nozzle_width: *matches.get_one::<f64>("NOZZLE").unwrap(),
+        cost_per_kg: matches.get_one::<f64>("COST").copied(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cli_test() {
+        cli().debug_assert();
+
+        // the subcommands bring their own '--help'
+        let err = cli()
+            .try_get_matches_from(["stl2thumbnail", "stl", "--help"])
+            .unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::DisplayHelp);
+    }
+
+    #[test]
+    fn stl_command_conflicts_test() {
+        // the lazy mesh cannot be repaired, the combination is rejected instead of ignored
+        let err = cli()
+            .try_get_matches_from([
+                "stl2thumbnail",
+                "stl",
+                "--lazy",
+                "--repair-normals",
+                "in.stl",
+                "out.png",
+            ])
+            .unwrap_err();
+        assert_eq!(err.kind(), clap::error::ErrorKind::ArgumentConflict);
+
+        assert!(cli()
+            .try_get_matches_from(["stl2thumbnail", "stl", "--repair-normals", "in.stl", "out.png"])
+            .is_ok());
+    }
+}
@@ -0,0 +1,194 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+
+use crate::stl::mesh::{Facet, Mesh, Triangle, Vec3};
+
+// ref: https://segeval.cs.princeton.edu/public/off_format.html
+//      http://www.geomview.org/docs/html/OFF.html
+
+pub fn read_mesh_from_file<P: AsRef<Path>>(filename: P) -> Result<Mesh> {
+    let data = std::fs::read(filename)?;
+    read_mesh_from_data(&data)
+}
+
+/// Reads an ascii OFF mesh (OFF, COFF, NOFF, CNOFF, ...)
+///
+/// Polygons are triangulated, vertex and face colors are attached to the triangles.
+pub fn read_mesh_from_data(data: &[u8]) -> Result<Mesh> {
+    let content = std::str::from_utf8(data)?;
+
+    // strip comments and empty lines
+    let mut lines = content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty());
+
+    let Some(first_line) = lines.next() else {
+        bail!("not an OFF file")
+    };
+
+    // the header keyword is optional and can be followed by the element counts
+    let mut tokens = first_line.split_ascii_whitespace().peekable();
+    let prefix = match tokens.peek() {
+        Some(keyword) if keyword.ends_with("OFF") => tokens.next().unwrap_or_default().trim_end_matches("OFF"),
+        _ => "",
+    };
+
+    if tokens.peek() == Some(&"BINARY") {
+        bail!("binary OFF files are not supported");
+    }
+    if prefix.contains(['4', 'n']) {
+        bail!("only three dimensional OFF files are supported");
+    }
+
+    let has_normals = prefix.contains('N');
+    let has_colors = prefix.contains('C');
+
+    let counts: Vec<usize> = match tokens.peek() {
+        Some(_) => tokens.map(str::parse).collect::<Result<_, _>>()?,
+        None => match lines.next() {
+            Some(line) => line
+                .split_ascii_whitespace()
+                .map(str::parse)
+                .collect::<Result<_, _>>()?,
+            None => bail!("missing element counts"),
+        },
+    };
+
+    let [vertex_count, face_count, ..] = counts[..] else {
+        bail!("missing element counts")
+    };
+
+    // the counts are not trusted, a vertex takes at least six bytes ("0 0 0\n")
+    let mut positions = Vec::with_capacity(vertex_count.min(data.len() / 6));
+    let mut colors = vec![];
+
+    for _ in 0..vertex_count {
+        let Some(line) = lines.next() else {
+            bail!("unexpected end of file")
+        };
+        let tokens: Vec<&str> = line.split_ascii_whitespace().collect();
+
+        let Some(values) = parse_floats(tokens.get(..3)) else {
+            bail!("invalid vertex '{line}'")
+        };
+        positions.push(Vec3::new(values[0], values[1], values[2]));
+
+        if has_colors {
+            // the color follows the position and the normal
+            let offset = if has_normals { 6 } else { 3 };
+            let Some(color) = parse_color(tokens.get(offset..)) else {
+                bail!("invalid vertex color '{line}'")
+            };
+            colors.push(color);
+        }
+    }
+
+    let mut triangles = Mesh::default();
+
+    for _ in 0..face_count {
+        let Some(line) = lines.next() else {
+            bail!("unexpected end of file")
+        };
+        let tokens: Vec<&str> = line.split_ascii_whitespace().collect();
+
+        let Some(count) = tokens.first().and_then(|t| t.parse::<usize>().ok()) else {
+            bail!("invalid face '{line}'")
+        };
+        let Some(indices) = tokens
+            .get(1..)
+            .and_then(|t| t.get(..count))
+            .and_then(|t| t.iter().map(|i| i.parse::<usize>().ok()).collect::<Option<Vec<_>>>())
+        else {
+            bail!("invalid face '{line}'")
+        };
+        if indices.iter().any(|&i| i >= positions.len()) {
+            bail!("vertex index out of range");
+        }
+
+        // the indices may be followed by a face color
+        let face_color = parse_color(tokens.get(count + 1..));
+
+        // triangulate the polygon as a fan
+        for i in 1..indices.len().saturating_sub(1) {
+            let corners = [indices[0], indices[i], indices[i + 1]];
+            let vertices = corners.map(|c| positions[c]);
+            let normal = (vertices[1] - vertices[0])
+                .cross(&(vertices[2] - vertices[0]))
+                .normalize();
+
+            let mut facet = Facet::from(Triangle::new(vertices, normal));
+            if let Some(color) = face_color {
+                facet = facet.with_colors([color; 3]);
+            } else if !colors.is_empty() {
+                facet = facet.with_colors(corners.map(|c| colors[c]));
+            }
+
+            triangles.push(facet);
+        }
+    }
+
+    Ok(triangles)
+}
+
+fn parse_floats(tokens: Option<&[&str]>) -> Option<Vec<f32>> {
+    tokens?.iter().map(|t| t.parse().ok()).collect()
+}
+
+/// Colors are either integers (0-255) or floats (0.0-1.0), the alpha channel is ignored
+fn parse_color(tokens: Option<&[&str]>) -> Option<Vec3> {
+    let tokens = tokens.filter(|t| t.len() >= 3)?;
+    let values = parse_floats(Some(&tokens[..3]))?;
+
+    let is_integer = tokens[..3].iter().all(|t| !t.contains(['.', 'e', 'E']));
+    let scale = if is_integer { 1.0 / 255.0 } else { 1.0 };
+
+    Some(Vec3::new(values[0], values[1], values[2]) * scale)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const QUAD_OFF: &[u8] = include_bytes!("../test_models/quad.off");
+
+    #[test]
+    fn read_off_test() {
+        let mesh = read_mesh_from_data(QUAD_OFF).unwrap();
+
+        assert_eq!(mesh.len(), 2);
+        assert_eq!(mesh[0].vertices[1], Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(mesh[1].vertices[2], Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(mesh[0].normal, Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(mesh.facet(0).colors, None);
+    }
+
+    #[test]
+    fn read_coff_test() {
+        let off = "COFF 3 2 0\n0 0 0 255 0 0 255\n1 0 0 0 255 0 255\n0 1 0 0 0 255 255\n\
+                   3 0 1 2\n3 0 2 1 1.0 1.0 1.0\n";
+        let mesh = read_mesh_from_data(off.as_bytes()).unwrap();
+
+        assert_eq!(mesh.len(), 2);
+
+        let colors = mesh.facet(0).colors.unwrap();
+        assert_eq!(colors[0], Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(colors[2], Vec3::new(0.0, 0.0, 1.0));
+
+        // face color
+        assert_eq!(mesh.facet(1).colors, Some([Vec3::new(1.0, 1.0, 1.0); 3]));
+    }
+
+    #[test]
+    fn read_invalid_off_test() {
+        assert!(read_mesh_from_data(b"OFF\n3 1 0\n0 0 0\n1 0 0\n").is_err());
+        assert!(read_mesh_from_data(b"OFF\n3 1 0\n0 0 0\n1 0 0\n0 1 0\n3 0 1 5\n").is_err());
+
+        // absurd counts fail instead of allocating or overflowing
+        assert!(read_mesh_from_data(format!("OFF\n{} 1 0\n0 0 0\n", usize::MAX).as_bytes()).is_err());
+        assert!(
+            read_mesh_from_data(format!("OFF\n3 1 0\n0 0 0\n1 0 0\n0 1 0\n{} 0 1 2\n", usize::MAX).as_bytes()).is_err()
+        );
+    }
+}
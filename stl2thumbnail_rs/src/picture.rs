@@ -339,6 +339,11 @@ impl Picture {
         }
     }
 
+    /// Draws pic over self with its top left corner at (x, y)
+    pub fn overlay(&mut self, pic: &Picture, x: i64, y: i64) {
+        image::imageops::overlay(&mut self.inner, &pic.inner, x, y);
+    }
+
     pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         self.inner.save_with_format(path, image::ImageFormat::Png)?;
 
@@ -501,6 +506,43 @@ impl Picture {
                 Vec2::new(1.0, 1.0),
             ],
 
+            'h' => vec![
+                Vec2::new(0.0, 0.0),
+                Vec2::new(0.0, 1.0),
+                Vec2::new(0.0, 0.5),
+                Vec2::new(1.0, 0.5),
+                Vec2::new(1.0, 0.5),
+                Vec2::new(1.0, 1.0),
+            ],
+
+            's' => vec![
+                Vec2::new(1.0, 0.5),
+                Vec2::new(0.0, 0.5),
+                Vec2::new(0.0, 0.5),
+                Vec2::new(0.0, 0.75),
+                Vec2::new(0.0, 0.75),
+                Vec2::new(1.0, 0.75),
+                Vec2::new(1.0, 0.75),
+                Vec2::new(1.0, 1.0),
+                Vec2::new(1.0, 1.0),
+                Vec2::new(0.0, 1.0),
+            ],
+
+            'g' => vec![
+                Vec2::new(0.0, 0.5),
+                Vec2::new(1.0, 0.5),
+                Vec2::new(1.0, 0.5),
+                Vec2::new(1.0, 1.0),
+                Vec2::new(1.0, 1.0),
+                Vec2::new(0.0, 1.0),
+                Vec2::new(0.0, 0.5),
+                Vec2::new(0.0, 0.75),
+                Vec2::new(0.0, 0.75),
+                Vec2::new(1.0, 0.75),
+            ],
+
+            '.' => vec![Vec2::new(0.5, 1.0), Vec2::new(0.5, 1.0)],
+
             _ => vec![],
         };
 
@@ -520,6 +562,32 @@ impl Picture {
             }
         }
     }
+
+    /// Draws a single line of text on a dark bar at the bottom of the picture, long text is shrunk to fit
+    pub fn draw_caption(&mut self, text: &str) {
+        let margin = 3;
+        let text_to_height_ratio = 16;
+
+        // shrink the text if it does not fit into a single line (see 'stroke_string' for the character advance)
+        let max_text_size = ((self.width() as f32 - 2.0 * margin as f32) / text.chars().count() as f32 - 6.0) / 0.7;
+        let text_size = (self.height() / text_to_height_ratio).min(max_text_size.max(0.0) as u32);
+
+        self.fill_rect(
+            0,
+            self.height() as i32 - (text_size + margin * 2) as i32,
+            self.width() as i32,
+            self.height() as i32,
+            &Color::DARK_GRAY,
+        );
+
+        self.stroke_string(
+            margin,
+            self.height() - text_size - margin,
+            text,
+            text_size as f32,
+            &Color::WHITE,
+        );
+    }
 }
 
 #[allow(unused_imports)]
@@ -548,14 +616,17 @@ mod tests {
         pic.thick_line(256, 0, 256, 512, &(1.0, 0.0, 0.0, 1.0).into(), 1.0);
 
         // plot chars
-        for (i, c) in ['0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'x', 'm']
-            .iter()
-            .enumerate()
+        for (i, c) in [
+            '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'x', 'm', 'h', 's', 'g', '.',
+        ]
+        .iter()
+        .enumerate()
         {
             pic.stroke_letter(100 + i as u32 * 14, 100, *c, 10.0, &"000000FF".try_into().unwrap());
         }
 
         pic.stroke_string(100, 200, "12x55mm", 10.0, &"E6E6E6FF".try_into().unwrap());
+        pic.stroke_string(100, 250, "1h38m 6.5g 0.2mm", 10.0, &"E6E6E6FF".try_into().unwrap());
 
         pic.save("test.png").unwrap();
     }
@@ -0,0 +1,386 @@
+use std::{
+    io::{BufRead, BufReader, Cursor, Read},
+    path::Path,
+    str::SplitAsciiWhitespace,
+};
+
+use anyhow::{bail, Result};
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
+
+use crate::stl::mesh::{Facet, Mesh, Triangle, Vec3};
+
+// ref: https://paulbourke.net/dataformats/ply/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PropertyType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl PropertyType {
+    fn parse(name: &str) -> Result<Self> {
+        Ok(match name {
+            "char" | "int8" => Self::Int8,
+            "uchar" | "uint8" => Self::UInt8,
+            "short" | "int16" => Self::Int16,
+            "ushort" | "uint16" => Self::UInt16,
+            "int" | "int32" => Self::Int32,
+            "uint" | "uint32" => Self::UInt32,
+            "float" | "float32" => Self::Float32,
+            "double" | "float64" => Self::Float64,
+            _ => bail!("unknown property type '{name}'"),
+        })
+    }
+
+    fn is_integer(&self) -> bool {
+        !matches!(self, Self::Float32 | Self::Float64)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Property {
+    Scalar {
+        name: String,
+        ty: PropertyType,
+    },
+    List {
+        name: String,
+        count_ty: PropertyType,
+        item_ty: PropertyType,
+    },
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match self {
+            Self::Scalar { name, .. } | Self::List { name, .. } => name,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn property_index(&self, names: &[&str]) -> Option<usize> {
+        self.properties.iter().position(|p| names.contains(&p.name()))
+    }
+}
+
+#[derive(Debug)]
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+}
+
+/// Reads the values of the body one after another
+enum Values<'a> {
+    Ascii(SplitAsciiWhitespace<'a>),
+    BinaryLittleEndian(Cursor<&'a [u8]>),
+    BinaryBigEndian(Cursor<&'a [u8]>),
+}
+
+impl Values<'_> {
+    fn next(&mut self, ty: PropertyType) -> Result<f64> {
+        match self {
+            Self::Ascii(tokens) => {
+                let Some(token) = tokens.next() else {
+                    bail!("unexpected end of file")
+                };
+                Ok(token.parse()?)
+            }
+            Self::BinaryLittleEndian(reader) => read_binary_value::<LittleEndian>(reader, ty),
+            Self::BinaryBigEndian(reader) => read_binary_value::<BigEndian>(reader, ty),
+        }
+    }
+}
+
+fn read_binary_value<B: ByteOrder>(reader: &mut Cursor<&[u8]>, ty: PropertyType) -> Result<f64> {
+    Ok(match ty {
+        PropertyType::Int8 => reader.read_i8()? as f64,
+        PropertyType::UInt8 => reader.read_u8()? as f64,
+        PropertyType::Int16 => reader.read_i16::<B>()? as f64,
+        PropertyType::UInt16 => reader.read_u16::<B>()? as f64,
+        PropertyType::Int32 => reader.read_i32::<B>()? as f64,
+        PropertyType::UInt32 => reader.read_u32::<B>()? as f64,
+        PropertyType::Float32 => reader.read_f32::<B>()? as f64,
+        PropertyType::Float64 => reader.read_f64::<B>()?,
+    })
+}
+
+pub fn read_mesh_from_file<P: AsRef<Path>>(filename: P) -> Result<Mesh> {
+    let file = std::fs::File::open(filename)?;
+    read_mesh(file)
+}
+
+/// Reads a PLY mesh (ascii, binary little endian or binary big endian)
+///
+/// Polygons are triangulated, vertex colors are attached to the triangles.
+pub fn read_mesh<R: Read>(r: R) -> Result<Mesh> {
+    let mut reader = BufReader::new(r);
+    let header = read_header(&mut reader)?;
+
+    let mut body = vec![];
+    reader.read_to_end(&mut body)?;
+
+    let mut values = match header.format {
+        Format::Ascii => Values::Ascii(std::str::from_utf8(&body)?.split_ascii_whitespace()),
+        Format::BinaryLittleEndian => Values::BinaryLittleEndian(Cursor::new(&body)),
+        Format::BinaryBigEndian => Values::BinaryBigEndian(Cursor::new(&body)),
+    };
+
+    let mut positions = vec![];
+    let mut colors = vec![];
+    let mut triangles = Mesh::default();
+
+    for element in &header.elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(&mut values, element, &mut positions, &mut colors)?,
+            "face" => read_faces(&mut values, element, &positions, &colors, &mut triangles)?,
+            _ => skip_element(&mut values, element)?,
+        }
+    }
+
+    Ok(triangles)
+}
+
+fn read_header<R: BufRead>(reader: &mut R) -> Result<Header> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if line.trim() != "ply" {
+        bail!("not a PLY file");
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            bail!("unexpected end of header");
+        }
+
+        let tokens: Vec<&str> = line.split_ascii_whitespace().collect();
+
+        match tokens.as_slice() {
+            ["end_header"] => break,
+            ["format", "ascii", _] => format = Some(Format::Ascii),
+            ["format", "binary_little_endian", _] => format = Some(Format::BinaryLittleEndian),
+            ["format", "binary_big_endian", _] => format = Some(Format::BinaryBigEndian),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse()?,
+                properties: vec![],
+            }),
+            ["property", "list", count_ty, item_ty, name] => {
+                let Some(element) = elements.last_mut() else {
+                    bail!("property without element")
+                };
+                element.properties.push(Property::List {
+                    name: name.to_string(),
+                    count_ty: PropertyType::parse(count_ty)?,
+                    item_ty: PropertyType::parse(item_ty)?,
+                });
+            }
+            ["property", ty, name] => {
+                let Some(element) = elements.last_mut() else {
+                    bail!("property without element")
+                };
+                element.properties.push(Property::Scalar {
+                    name: name.to_string(),
+                    ty: PropertyType::parse(ty)?,
+                });
+            }
+            // comments, obj_info, empty lines
+            _ => (),
+        }
+    }
+
+    let Some(format) = format else {
+        bail!("missing PLY format")
+    };
+
+    Ok(Header { format, elements })
+}
+
+/// Reads all properties of an element, lists are returned as a whole
+fn read_element(values: &mut Values, element: &Element, row: &mut Vec<Vec<f64>>) -> Result<()> {
+    row.clear();
+
+    for property in &element.properties {
+        match property {
+            Property::Scalar { ty, .. } => row.push(vec![values.next(*ty)?]),
+            Property::List { count_ty, item_ty, .. } => {
+                let count = values.next(*count_ty)? as usize;
+                let items = (0..count).map(|_| values.next(*item_ty)).collect::<Result<Vec<_>>>()?;
+                row.push(items);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn skip_element(values: &mut Values, element: &Element) -> Result<()> {
+    let mut row = vec![];
+    for _ in 0..element.count {
+        read_element(values, element, &mut row)?;
+    }
+
+    Ok(())
+}
+
+fn read_vertices(
+    values: &mut Values,
+    element: &Element,
+    positions: &mut Vec<Vec3>,
+    colors: &mut Vec<Vec3>,
+) -> Result<()> {
+    let (Some(x), Some(y), Some(z)) = (
+        element.property_index(&["x"]),
+        element.property_index(&["y"]),
+        element.property_index(&["z"]),
+    ) else {
+        bail!("vertex without position")
+    };
+
+    let color = match (
+        element.property_index(&["red", "diffuse_red"]),
+        element.property_index(&["green", "diffuse_green"]),
+        element.property_index(&["blue", "diffuse_blue"]),
+    ) {
+        (Some(r), Some(g), Some(b)) => Some([r, g, b]),
+        _ => None,
+    };
+
+    // integer colors are in the range 0-255, floating point colors in the range 0.0-1.0
+    let color_scale = match color.map(|c| &element.properties[c[0]]) {
+        Some(Property::Scalar { ty, .. }) if ty.is_integer() => 1.0 / 255.0,
+        _ => 1.0,
+    };
+
+    let mut row = vec![];
+    for _ in 0..element.count {
+        read_element(values, element, &mut row)?;
+
+        let value = |i: usize| row[i].first().copied().unwrap_or_default() as f32;
+        positions.push(Vec3::new(value(x), value(y), value(z)));
+
+        if let Some([r, g, b]) = color {
+            colors.push(Vec3::new(value(r), value(g), value(b)) * color_scale);
+        }
+    }
+
+    Ok(())
+}
+
+fn read_faces(
+    values: &mut Values,
+    element: &Element,
+    positions: &[Vec3],
+    colors: &[Vec3],
+    triangles: &mut Mesh,
+) -> Result<()> {
+    let Some(indices) = element.property_index(&["vertex_indices", "vertex_index"]) else {
+        bail!("face without vertex indices")
+    };
+
+    let mut row = vec![];
+    for _ in 0..element.count {
+        read_element(values, element, &mut row)?;
+
+        let face = &row[indices];
+        if face.iter().any(|&i| i < 0.0 || i as usize >= positions.len()) {
+            bail!("vertex index out of range");
+        }
+
+        // triangulate the polygon as a fan
+        for i in 1..face.len().saturating_sub(1) {
+            let corners = [face[0] as usize, face[i] as usize, face[i + 1] as usize];
+            let vertices = corners.map(|c| positions[c]);
+            let normal = (vertices[1] - vertices[0])
+                .cross(&(vertices[2] - vertices[0]))
+                .normalize();
+
+            let mut facet = Facet::from(Triangle::new(vertices, normal));
+            if !colors.is_empty() {
+                facet = facet.with_colors(corners.map(|c| colors[c]));
+            }
+
+            triangles.push(facet);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const QUAD_ASCII: &[u8] = include_bytes!("../test_models/quad_ascii.ply");
+    const QUAD_BIN_LE: &[u8] = include_bytes!("../test_models/quad_binary_le.ply");
+    const QUAD_BIN_BE: &[u8] = include_bytes!("../test_models/quad_binary_be.ply");
+
+    fn check_quad(mesh: &Mesh) {
+        assert_eq!(mesh.len(), 2);
+
+        assert_eq!(mesh[0].vertices[0], Vec3::new(0.0, 0.0, 0.0));
+        assert_eq!(mesh[0].vertices[1], Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(mesh[0].vertices[2], Vec3::new(1.0, 1.0, 0.0));
+        assert_eq!(mesh[1].vertices[2], Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(mesh[0].normal, Vec3::new(0.0, 0.0, 1.0));
+
+        let colors = mesh.facet(0).colors.unwrap();
+        assert_eq!(colors[0], Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(colors[1], Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn read_ascii_test() {
+        check_quad(&read_mesh(QUAD_ASCII).unwrap());
+    }
+
+    #[test]
+    fn read_binary_test() {
+        check_quad(&read_mesh(QUAD_BIN_LE).unwrap());
+        check_quad(&read_mesh(QUAD_BIN_BE).unwrap());
+    }
+
+    #[test]
+    fn read_without_colors_test() {
+        let ply = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\n\
+                   element face 1\nproperty list uchar int vertex_indices\nend_header\n\
+                   0 0 0\n1 0 0\n0 1 0\n3 0 1 2\n";
+        let mesh = read_mesh(ply.as_bytes()).unwrap();
+
+        assert_eq!(mesh.len(), 1);
+        assert_eq!(mesh.facet(0).colors, None);
+    }
+
+    #[test]
+    fn read_invalid_index_test() {
+        let ply = "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nproperty float y\nproperty float z\n\
+                   element face 1\nproperty list uchar int vertex_indices\nend_header\n\
+                   0 0 0\n3 0 1 2\n";
+
+        assert!(read_mesh(ply.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,382 @@
+use std::{
+    io::{Cursor, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use anyhow::{bail, Result};
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
+
+use crate::picture::{Color, Picture};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResinType {
+    /// Prusa SL1, SL1S
+    Sl1,
+    /// Chitubox CTB and CBDDLP
+    Ctb,
+    /// Anycubic Photon Workshop (PWMX, PWMO, PWS, ...)
+    Pwmx,
+    /// Elegoo GOO
+    Goo,
+}
+
+const CBDDLP_MAGIC: u32 = 0x12FD0019;
+const CTB_MAGIC: u32 = 0x12FD0086;
+const CTB_ENCRYPTED_MAGIC: u32 = 0x12FD0107;
+const PWMX_MAGIC: &[u8] = b"ANYCUBIC";
+const GOO_MAGIC: &[u8] = &[0x07, 0x00, 0x00, 0x00, 0x44, 0x4C, 0x50, 0x00];
+
+/// File extensions of the supported slice files, the type itself is detected from the content
+pub const EXTENSIONS: &[&str] = &[
+    "sl1", "sl1s", "ctb", "cbddlp", "pwmx", "pwmo", "pws", "pw0", "pwms", "pwma", "pwmb", "pmx2", "pm3", "pm3m",
+    "pm3r", "goo",
+];
+
+// previews larger than this are considered corrupt
+const MAX_PREVIEW_SIZE: u32 = 4096;
+
+fn detect_format(data: &[u8]) -> Result<ResinType> {
+    if data.starts_with(b"PK\x03\x04") {
+        return Ok(ResinType::Sl1);
+    }
+
+    if data.starts_with(PWMX_MAGIC) {
+        return Ok(ResinType::Pwmx);
+    }
+
+    if data.get(4..12) == Some(GOO_MAGIC) {
+        return Ok(ResinType::Goo);
+    }
+
+    match Cursor::new(data).read_u32::<LittleEndian>() {
+        Ok(CBDDLP_MAGIC | CTB_MAGIC) => Ok(ResinType::Ctb),
+        Ok(CTB_ENCRYPTED_MAGIC) => bail!("encrypted ctb files are not supported"),
+        _ => bail!("cannot determine resin file type"),
+    }
+}
+
+pub fn extract_previews_from_file<P: AsRef<Path>>(filename: P) -> Result<Vec<Picture>> {
+    let data = std::fs::read(filename)?;
+
+    extract_previews_from_data(&data)
+}
+
+/// Extracts the previews of a resin slice file (sorted by size in ascending order)
+pub fn extract_previews_from_data(data: &[u8]) -> Result<Vec<Picture>> {
+    let mut pictures = match detect_format(data)? {
+        ResinType::Sl1 => extract_previews_sl1(data)?,
+        ResinType::Ctb => extract_previews_ctb(data)?,
+        ResinType::Pwmx => extract_previews_pwmx(data)?,
+        ResinType::Goo => extract_previews_goo(data)?,
+    };
+
+    pictures.sort_by_key(|a| a.width() * a.height());
+
+    Ok(pictures)
+}
+
+pub fn extract_previews_sl1(data: &[u8]) -> Result<Vec<Picture>> {
+    // zip archive
+    // ...
+    // thumbnail/thumbnail400x400.png
+    // thumbnail/thumbnail800x480.png
+    let mut archive = zip::ZipArchive::new(Cursor::new(data))?;
+
+    let thumbnails: Vec<String> = archive
+        .file_names()
+        .filter(|name| name.starts_with("thumbnail/") && name.ends_with(".png"))
+        .map(str::to_string)
+        .collect();
+
+    let mut pictures = vec![];
+
+    for thumbnail in thumbnails {
+        let mut buffer = vec![];
+        archive.by_name(&thumbnail)?.read_to_end(&mut buffer)?;
+        let image = image::load_from_memory(&buffer)?;
+
+        pictures.push(Picture::from_img_buffer(image.to_rgba8()));
+    }
+
+    Ok(pictures)
+}
+
+pub fn extract_previews_ctb(data: &[u8]) -> Result<Vec<Picture>> {
+    // ref: https://github.com/sn4k3/UVtools/blob/master/UVtools.Core/FileFormats/ChituboxFile.cs
+    //
+    // the header stores the offsets of the large (0x3C) and the small (0x48) preview
+    let mut reader = Cursor::new(data);
+
+    reader.seek(SeekFrom::Start(0x3C))?;
+    let large_preview_offset = reader.read_u32::<LittleEndian>()?;
+    reader.seek(SeekFrom::Start(0x48))?;
+    let small_preview_offset = reader.read_u32::<LittleEndian>()?;
+
+    let mut pictures = vec![];
+
+    for offset in [small_preview_offset, large_preview_offset] {
+        if offset == 0 {
+            continue;
+        }
+
+        // preview header: width (u32), height (u32), image offset (u32), image length (u32)
+        reader.seek(SeekFrom::Start(offset as u64))?;
+        let width = reader.read_u32::<LittleEndian>()?;
+        let height = reader.read_u32::<LittleEndian>()?;
+        let image_offset = reader.read_u32::<LittleEndian>()? as usize;
+        let image_length = reader.read_u32::<LittleEndian>()? as usize;
+
+        let Some(image_data) = data.get(image_offset..image_offset + image_length) else {
+            bail!("preview exceeds file size")
+        };
+
+        pictures.push(decode_rle_rgb15(image_data, width, height)?);
+    }
+
+    Ok(pictures)
+}
+
+pub fn extract_previews_pwmx(data: &[u8]) -> Result<Vec<Picture>> {
+    // ref: https://github.com/sn4k3/UVtools/blob/master/UVtools.Core/FileFormats/PhotonWorkshopFile.cs
+    //
+    // file mark: 'ANYCUBIC' (12 bytes), version, area count, header address, padding, preview address, ...
+    // preview: 'PREVIEW' (12 bytes), section length, width, 'x' (4 bytes), height, RGB565 data
+    let mut reader = Cursor::new(data);
+
+    reader.seek(SeekFrom::Start(28))?;
+    let preview_address = reader.read_u32::<LittleEndian>()? as u64;
+
+    reader.seek(SeekFrom::Start(preview_address))?;
+    let mut section = [0u8; 12];
+    reader.read_exact(&mut section)?;
+    if !section.starts_with(b"PREVIEW") {
+        bail!("cannot find preview section");
+    }
+
+    let _length = reader.read_u32::<LittleEndian>()?;
+    let width = reader.read_u32::<LittleEndian>()?;
+    let _mark = reader.read_u32::<LittleEndian>()?;
+    let height = reader.read_u32::<LittleEndian>()?;
+
+    let start = reader.position() as usize;
+    let picture = decode_rgb565::<LittleEndian>(&data[start..], width, height)?;
+
+    Ok(vec![picture])
+}
+
+pub fn extract_previews_goo(data: &[u8]) -> Result<Vec<Picture>> {
+    // ref: https://github.com/elegooofficial/GOO
+    //
+    // big endian
+    // header: version (4 bytes), magic (8 bytes), software info (32 bytes), software version (24 bytes),
+    //         creation time (24 bytes), machine name (32 bytes), machine type (32 bytes), profile name (32 bytes),
+    //         anti-aliasing, grey and blur level (3x u16),
+    //         small preview (116x116 RGB565), delimiter (2 bytes), big preview (290x290 RGB565), delimiter (2 bytes)
+    let small_preview_start = 194;
+    let small_preview_size = (116, 116);
+    let big_preview_start = small_preview_start + small_preview_size.0 * small_preview_size.1 * 2 + 2;
+    let big_preview_size = (290, 290);
+
+    let Some(small_preview_data) = data.get(small_preview_start..) else {
+        bail!("preview exceeds file size")
+    };
+    let Some(big_preview_data) = data.get(big_preview_start..) else {
+        bail!("preview exceeds file size")
+    };
+
+    Ok(vec![
+        decode_rgb565::<BigEndian>(
+            small_preview_data,
+            small_preview_size.0 as u32,
+            small_preview_size.1 as u32,
+        )?,
+        decode_rgb565::<BigEndian>(big_preview_data, big_preview_size.0 as u32, big_preview_size.1 as u32)?,
+    ])
+}
+
+/// Decodes Chitubox' run-length encoded RGB15 previews
+fn decode_rle_rgb15(data: &[u8], width: u32, height: u32) -> Result<Picture> {
+    if width > MAX_PREVIEW_SIZE || height > MAX_PREVIEW_SIZE {
+        bail!("invalid preview size {width}x{height}");
+    }
+
+    let mut pic = Picture::new(width, height, &Color::TRANSPARENT);
+    let pixel_count = width * height;
+    let mut pixel = 0;
+
+    // each run starts with a color: RRRRRGGGGGXBBBBB
+    // where X indicates a repeat count in the following u16 (lower 12 bits)
+    let mut reader = Cursor::new(data);
+
+    while let Ok(dot) = reader.read_u16::<LittleEndian>() {
+        let color = Color {
+            r: (((dot >> 11) & 0x1F) << 3) as u8,
+            g: (((dot >> 6) & 0x1F) << 3) as u8,
+            b: ((dot & 0x1F) << 3) as u8,
+            a: 255,
+        };
+
+        let mut repeat = 1;
+        if dot & 0x0020 != 0 {
+            repeat += (reader.read_u16::<LittleEndian>()? & 0x0FFF) as u32;
+        }
+
+        for _ in 0..repeat.min(pixel_count - pixel) {
+            pic.set(pixel % width, pixel / width, &color);
+            pixel += 1;
+        }
+    }
+
+    Ok(pic)
+}
+
+/// Decodes uncompressed RGB565 previews
+fn decode_rgb565<B: ByteOrder>(data: &[u8], width: u32, height: u32) -> Result<Picture> {
+    if width > MAX_PREVIEW_SIZE || height > MAX_PREVIEW_SIZE {
+        bail!("invalid preview size {width}x{height}");
+    }
+
+    let Some(data) = data.get(..(width * height * 2) as usize) else {
+        bail!("preview exceeds file size")
+    };
+
+    let mut pic = Picture::new(width, height, &Color::TRANSPARENT);
+
+    for (i, rgb) in data.chunks_exact(2).enumerate() {
+        let rgb = B::read_u16(rgb);
+        let color = Color {
+            r: ((rgb >> 11) << 3) as u8,
+            g: (((rgb >> 5) & 0x3F) << 2) as u8,
+            b: ((rgb & 0x1F) << 3) as u8,
+            a: 255,
+        };
+
+        pic.set(i as u32 % width, i as u32 / width, &color);
+    }
+
+    Ok(pic)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use byteorder::WriteBytesExt;
+    use std::io::Write;
+
+    #[test]
+    fn test_decode_rle_rgb15() {
+        // 3x red, 1x blue
+        let mut data = vec![];
+        data.write_u16::<LittleEndian>(0xF800 | 0x0020).unwrap();
+        data.write_u16::<LittleEndian>(0x3000 | 2).unwrap();
+        data.write_u16::<LittleEndian>(0x001F).unwrap();
+
+        let pic = decode_rle_rgb15(&data, 2, 2).unwrap();
+
+        assert_eq!(pic.get(0, 0), (248, 0, 0, 255).into());
+        assert_eq!(pic.get(0, 1), (248, 0, 0, 255).into());
+        assert_eq!(pic.get(1, 1), (0, 0, 248, 255).into());
+    }
+
+    #[test]
+    fn test_extract_previews_ctb() {
+        let mut data = vec![0u8; 0x70];
+        (&mut data[0..4]).write_u32::<LittleEndian>(CTB_MAGIC).unwrap();
+        (&mut data[0x3C..0x40]).write_u32::<LittleEndian>(0x70).unwrap();
+
+        // preview header followed by a single run of green pixels
+        data.write_u32::<LittleEndian>(4).unwrap();
+        data.write_u32::<LittleEndian>(2).unwrap();
+        data.write_u32::<LittleEndian>(0x70 + 32).unwrap();
+        data.write_u32::<LittleEndian>(4).unwrap();
+        data.extend_from_slice(&[0u8; 16]);
+        data.write_u16::<LittleEndian>(0x07C0 | 0x0020).unwrap();
+        data.write_u16::<LittleEndian>(0x3000 | 7).unwrap();
+
+        assert_eq!(detect_format(&data).unwrap(), ResinType::Ctb);
+
+        let previews = extract_previews_from_data(&data).unwrap();
+        assert_eq!(previews.len(), 1);
+        assert_eq!(previews[0].width(), 4);
+        assert_eq!(previews[0].get(3, 1), (0, 248, 0, 255).into());
+    }
+
+    #[test]
+    fn test_extract_previews_pwmx() {
+        let mut data = PWMX_MAGIC.to_vec();
+        data.resize(28, 0);
+        data.write_u32::<LittleEndian>(32).unwrap();
+        data.extend_from_slice(b"PREVIEW\0\0\0\0\0");
+        data.write_u32::<LittleEndian>(12 + 2 * 2 * 2).unwrap();
+        data.write_u32::<LittleEndian>(2).unwrap();
+        data.extend_from_slice(b"x\0\0\0");
+        data.write_u32::<LittleEndian>(2).unwrap();
+        for _ in 0..4 {
+            data.write_u16::<LittleEndian>(0xFFFF).unwrap();
+        }
+
+        assert_eq!(detect_format(&data).unwrap(), ResinType::Pwmx);
+
+        let previews = extract_previews_from_data(&data).unwrap();
+        assert_eq!(previews.len(), 1);
+        assert_eq!(previews[0].get(1, 1), (248, 252, 248, 255).into());
+    }
+
+    #[test]
+    fn test_extract_previews_goo() {
+        let mut data = b"V3.0".to_vec();
+        data.extend_from_slice(GOO_MAGIC);
+        data.resize(194, 0);
+        for _ in 0..116 * 116 {
+            data.write_u16::<BigEndian>(0xF800).unwrap();
+        }
+        data.extend_from_slice(b"\r\n");
+        for _ in 0..290 * 290 {
+            data.write_u16::<BigEndian>(0x001F).unwrap();
+        }
+        data.extend_from_slice(b"\r\n");
+
+        assert_eq!(detect_format(&data).unwrap(), ResinType::Goo);
+
+        let previews = extract_previews_from_data(&data).unwrap();
+        assert_eq!(previews.len(), 2);
+        assert_eq!(previews[0].width(), 116);
+        assert_eq!(previews[0].get(10, 10), (248, 0, 0, 255).into());
+        assert_eq!(previews[1].width(), 290);
+        assert_eq!(previews[1].get(10, 10), (0, 0, 248, 255).into());
+    }
+
+    #[test]
+    fn test_extract_previews_sl1() {
+        let mut png = vec![];
+        image::RgbaImage::new(40, 40)
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+
+        let mut writer = zip::ZipWriter::new(Cursor::new(vec![]));
+        writer
+            .start_file("thumbnail/thumbnail40x40.png", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(&png).unwrap();
+        writer
+            .start_file("config.ini", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"layerHeight = 0.05").unwrap();
+        let data = writer.finish().unwrap().into_inner();
+
+        assert_eq!(detect_format(&data).unwrap(), ResinType::Sl1);
+
+        let previews = extract_previews_from_data(&data).unwrap();
+        assert_eq!(previews.len(), 1);
+        assert_eq!(previews[0].width(), 40);
+    }
+
+    #[test]
+    fn test_detect_format() {
+        let mut data = vec![];
+        data.write_u32::<LittleEndian>(CTB_ENCRYPTED_MAGIC).unwrap();
+        assert!(detect_format(&data).is_err());
+
+        assert!(detect_format(b"G28").is_err());
+    }
+}
@@ -11,11 +11,11 @@ impl AABB {
         Self::from_iterable(mesh)
     }
 
-    pub fn from_iterable(mesh: impl IntoIterator<Item = Triangle> + Copy) -> Self {
+    pub fn from_iterable(mesh: impl IntoIterator<Item = Facet>) -> Self {
         let mut lower = Vec3::new(f32::MAX, f32::MAX, f32::MAX);
         let mut upper = Vec3::new(f32::MIN, f32::MIN, f32::MIN);
 
-        for t in mesh {
+        for Facet { triangle: t, .. } in mesh {
             let v = &t.vertices;
 
             lower.x = lower.x.min(v[0].x.min(v[1].x).min(v[2].x));
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use super::aabb::AABB;
+use super::mesh::{Facet, Triangle, Vec3};
+
+/// Geometric properties of a mesh, lengths are in model units (usually mm)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MeshStats {
+    pub triangle_count: usize,
+    /// signed volume, negative if the triangles are oriented inwards
+    pub volume: f64,
+    pub surface_area: f64,
+    /// `None` for empty meshes
+    pub bounding_box: Option<BoundingBox>,
+    /// edges with a single adjacent triangle (holes)
+    pub boundary_edges: usize,
+    /// edges with more than two adjacent triangles
+    pub non_manifold_edges: usize,
+    /// number of connected parts
+    pub shells: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BoundingBox {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+    pub size: [f32; 3],
+}
+
+pub fn analyze(mesh: impl IntoIterator<Item = Facet> + Copy) -> MeshStats {
+    let mut triangle_count = 0;
+    let mut volume = 0.0;
+    let mut surface_area = 0.0;
+
+    // vertices are welded if their coordinates match exactly
+    let mut vertex_ids: HashMap<[u32; 3], usize> = HashMap::new();
+    let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut shells = UnionFind::default();
+
+    for Facet { triangle: t, .. } in mesh {
+        triangle_count += 1;
+
+        let (v, a) = triangle_volume_and_area(&t);
+        volume += v;
+        surface_area += a;
+
+        let ids = t.vertices.map(|v| {
+            let next_id = vertex_ids.len();
+            *vertex_ids.entry(vertex_key(&v)).or_insert(next_id)
+        });
+
+        for (i, j) in [(0, 1), (1, 2), (2, 0)] {
+            let (a, b) = (ids[i], ids[j]);
+            if a != b {
+                *edges.entry((a.min(b), a.max(b))).or_default() += 1;
+            }
+
+            shells.union(a, b);
+        }
+    }
+
+    let bounding_box = (triangle_count > 0).then(|| {
+        let aabb = AABB::from_iterable(mesh);
+        BoundingBox {
+            min: aabb.lower.into(),
+            max: aabb.upper.into(),
+            size: aabb.size().into(),
+        }
+    });
+
+    MeshStats {
+        triangle_count,
+        volume,
+        surface_area,
+        bounding_box,
+        boundary_edges: edges.values().filter(|&&count| count == 1).count(),
+        non_manifold_edges: edges.values().filter(|&&count| count > 2).count(),
+        shells: (0..vertex_ids.len()).filter(|&id| shells.find(id) == id).count(),
+    }
+}
+
+/// Print parameters for the material estimate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaterialSettings {
+    /// density in g/cm³
+    pub density: f64,
+    /// infill fraction of the interior (0.0-1.0), 1.0 for resin prints
+    pub infill: f64,
+    pub wall_count: u32,
+    /// extrusion width in mm
+    pub nozzle_width: f64,
+    /// material cost per kg
+    pub cost_per_kg: Option<f64>,
+}
+
+impl Default for MaterialSettings {
+    fn default() -> Self {
+        // PLA
+        Self {
+            density: 1.24,
+            infill: 0.2,
+            wall_count: 2,
+            nozzle_width: 0.4,
+            cost_per_kg: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct MaterialEstimate {
+    /// material volume in cm³
+    pub volume_cm3: f64,
+    /// weight in g
+    pub weight_g: f64,
+    pub cost: Option<f64>,
+}
+
+/// Signed volume and surface area in a single pass, cheaper than `analyze`
+pub fn volume_and_area(mesh: impl IntoIterator<Item = Facet>) -> (f64, f64) {
+    mesh.into_iter()
+        .map(|f| triangle_volume_and_area(&f.triangle))
+        .fold((0.0, 0.0), |(volume, area), (v, a)| (volume + v, area + a))
+}
+
+/// Signed volume of the tetrahedron with the origin and the area of the triangle
+fn triangle_volume_and_area(t: &Triangle) -> (f64, f64) {
+    let [a, b, c] = t.vertices.map(|v| v.map(|c| c as f64));
+    (a.dot(&b.cross(&c)) / 6.0, (b - a).cross(&(c - a)).norm() / 2.0)
+}
+
+/// Estimates the material used to print a mesh (in mm) from its volume and surface area
+///
+/// The walls are approximated by the surface area times the wall thickness,
+/// the remaining interior is filled according to the infill fraction.
+pub fn estimate_material(volume: f64, surface_area: f64, settings: &MaterialSettings) -> MaterialEstimate {
+    let volume = volume.abs();
+
+    let shell_volume = (surface_area * settings.wall_count as f64 * settings.nozzle_width).min(volume);
+    let infill_volume = (volume - shell_volume) * settings.infill.clamp(0.0, 1.0);
+
+    let volume_cm3 = (shell_volume + infill_volume) / 1000.0;
+    let weight_g = volume_cm3 * settings.density;
+
+    MaterialEstimate {
+        volume_cm3,
+        weight_g,
+        cost: settings.cost_per_kg.map(|cost| weight_g / 1000.0 * cost),
+    }
+}
+
+pub(crate) fn vertex_key(v: &Vec3) -> [u32; 3] {
+    // -0.0 and 0.0 are the same vertex
+    [v.x, v.y, v.z].map(|c| (c + 0.0).to_bits())
+}
+
+#[derive(Default)]
+struct UnionFind {
+    parents: Vec<usize>,
+}
+
+impl UnionFind {
+    fn find(&mut self, id: usize) -> usize {
+        if id >= self.parents.len() {
+            self.parents.extend(self.parents.len()..=id);
+        }
+
+        // path halving
+        let mut id = id;
+        while self.parents[id] != id {
+            self.parents[id] = self.parents[self.parents[id]];
+            id = self.parents[id];
+        }
+        id
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.parents[a.max(b)] = a.min(b);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::stl::mesh::test::cuboid;
+    use crate::stl::mesh::Mesh;
+
+    #[test]
+    fn analyze_cube_test() {
+        let mesh = Mesh::new(cuboid(Vec3::new(1.0, 2.0, 3.0), Vec3::repeat(1.0)));
+        let stats = analyze(&mesh);
+
+        assert_eq!(stats.triangle_count, 12);
+        assert!((stats.volume - 1.0).abs() < 1e-9);
+        assert!((stats.surface_area - 6.0).abs() < 1e-9);
+        assert_eq!(volume_and_area(&mesh), (stats.volume, stats.surface_area));
+        assert_eq!(stats.boundary_edges, 0);
+        assert_eq!(stats.non_manifold_edges, 0);
+        assert_eq!(stats.shells, 1);
+        assert_eq!(
+            stats.bounding_box,
+            Some(BoundingBox {
+                min: [1.0, 2.0, 3.0],
+                max: [2.0, 3.0, 4.0],
+                size: [1.0, 1.0, 1.0],
+            })
+        );
+    }
+
+    #[test]
+    fn analyze_open_and_separate_shells_test() {
+        // a cube with a missing triangle
+        let mut triangles = cuboid(Vec3::zeros(), Vec3::repeat(1.0));
+        triangles.pop();
+
+        let stats = analyze(&Mesh::new(triangles.clone()));
+        assert_eq!(stats.boundary_edges, 3);
+
+        // two cubes, one of them shares an edge with the open one
+        triangles.extend(cuboid(Vec3::new(3.0, 0.0, 0.0), Vec3::repeat(1.0)));
+        triangles.extend(cuboid(Vec3::new(1.0, 1.0, 0.0), Vec3::repeat(1.0)));
+
+        let stats = analyze(&Mesh::new(triangles));
+        assert_eq!(stats.shells, 2);
+        assert_eq!(stats.non_manifold_edges, 1);
+    }
+
+    #[test]
+    fn estimate_material_test() {
+        // 10mm cube
+        let triangles = cuboid(Vec3::zeros(), Vec3::repeat(1.0))
+            .into_iter()
+            .map(|t| Triangle::new(t.vertices.map(|v| v * 10.0), t.normal))
+            .collect();
+        let (volume, area) = volume_and_area(&Mesh::new(triangles));
+
+        let settings = MaterialSettings {
+            density: 1.0,
+            infill: 0.5,
+            wall_count: 1,
+            nozzle_width: 1.0,
+            cost_per_kg: Some(20.0),
+        };
+        let estimate = estimate_material(volume, area, &settings);
+
+        // 600mm³ of walls and half of the remaining 400mm³
+        assert!((estimate.volume_cm3 - 0.8).abs() < 1e-9);
+        assert!((estimate.weight_g - 0.8).abs() < 1e-9);
+        assert!((estimate.cost.unwrap() - 0.016).abs() < 1e-9);
+
+        // the walls cannot exceed the volume
+        let settings = MaterialSettings {
+            wall_count: 10,
+            ..settings
+        };
+        assert!((estimate_material(volume, area, &settings).volume_cm3 - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn analyze_empty_test() {
+        let stats = analyze(&Mesh::new(vec![]));
+
+        assert_eq!(stats.triangle_count, 0);
+        assert_eq!(stats.shells, 0);
+        assert_eq!(stats.bounding_box, None);
+    }
+}
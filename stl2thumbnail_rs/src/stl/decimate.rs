@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+
+use super::mesh::{Facet, Mesh, Vec3};
+
+/// Simplifies a mesh by merging the vertices within the cells of a grid (vertex clustering)
+///
+/// Triangles collapsing to a line or a point are dropped, details smaller than a cell vanish.
+pub fn decimate(mesh: impl IntoIterator<Item = Facet> + Copy, cell_size: f32) -> Mesh {
+    // every cell is represented by the average of its vertices
+    let mut cells: HashMap<[i32; 3], u32, FastHash> = HashMap::default();
+    let mut clusters: Vec<(Vec3, u32)> = vec![];
+
+    // tiny triangles mostly share the cell of the previous vertex
+    let mut last: Option<([i32; 3], u32)> = None;
+
+    let corners: Vec<[u32; 3]> = mesh
+        .into_iter()
+        .map(|f| {
+            f.triangle.vertices.map(|v| {
+                let cell: [i32; 3] = (v / cell_size).map(|c| c.floor() as i32).into();
+                let cluster = match last {
+                    Some((last_cell, cluster)) if last_cell == cell => cluster,
+                    _ => *cells.entry(cell).or_insert_with(|| {
+                        clusters.push((Vec3::zeros(), 0));
+                        clusters.len() as u32 - 1
+                    }),
+                };
+                last = Some((cell, cluster));
+
+                let (sum, count) = &mut clusters[cluster as usize];
+                *sum += v;
+                *count += 1;
+
+                cluster
+            })
+        })
+        .collect();
+
+    let positions: Vec<Vec3> = clusters.iter().map(|(sum, count)| sum / *count as f32).collect();
+
+    let mut decimated = Mesh::default();
+
+    for (mut facet, corners) in mesh.into_iter().zip(corners) {
+        let [a, b, c] = corners;
+        if a == b || b == c || c == a {
+            continue;
+        }
+
+        let t = facet.triangle;
+        let triangle = &mut facet.triangle;
+        triangle.vertices = corners.map(|c| positions[c as usize]);
+
+        let normal = (triangle.vertices[1] - triangle.vertices[0])
+            .cross(&(triangle.vertices[2] - triangle.vertices[0]))
+            .normalize();
+
+        if !normal.iter().all(|c| c.is_finite()) {
+            continue;
+        }
+
+        // keep facing the same way if the triangle got folded over
+        if normal.dot(&t.normal) < 0.0 {
+            triangle.vertices.swap(1, 2);
+            triangle.normal = -normal;
+            if let Some(colors) = &mut facet.colors {
+                colors.swap(1, 2);
+            }
+        } else {
+            triangle.normal = normal;
+        }
+
+        decimated.push(facet);
+    }
+
+    decimated
+}
+
+/// Multiplicative hashing, SipHash is slow for the small integer keys
+type FastHash = BuildHasherDefault<FastHasher>;
+
+#[derive(Default)]
+struct FastHasher(u64);
+
+impl Hasher for FastHasher {
+    fn finish(&self) -> u64 {
+        // the table is indexed by the low bits, which the multiplication mixes poorly
+        self.0.rotate_left(26)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_u64(byte as u64);
+        }
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write_u64(i as u32 as u64);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.0 = (self.0.rotate_left(5) ^ i).wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::stl::aabb::AABB;
+    use crate::stl::mesh::Triangle;
+
+    /// A flat square made of n x n quads
+    fn grid(n: usize) -> Mesh {
+        let v = |x: usize, y: usize| Vec3::new(x as f32 / n as f32, y as f32 / n as f32, 0.0);
+        let up = Vec3::new(0.0, 0.0, 1.0);
+
+        let mut triangles = vec![];
+        for x in 0..n {
+            for y in 0..n {
+                triangles.push(Triangle::new([v(x, y), v(x + 1, y), v(x + 1, y + 1)], up));
+                triangles.push(Triangle::new([v(x, y), v(x + 1, y + 1), v(x, y + 1)], up));
+            }
+        }
+
+        Mesh::new(triangles)
+    }
+
+    #[test]
+    fn decimate_grid_test() {
+        let mesh = grid(100);
+        let decimated = decimate(&mesh, 0.1);
+
+        assert_eq!(mesh.len(), 20000);
+        assert!(decimated.len() <= 2 * 11 * 11, "{}", decimated.len());
+        assert!(decimated.len() >= 100);
+
+        let aabb = AABB::from_mesh(&decimated);
+        assert!(aabb.size().x > 0.9);
+        assert!(aabb.size().y > 0.9);
+
+        assert!((&decimated)
+            .into_iter()
+            .all(|f| f.triangle.normal == Vec3::new(0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn decimate_small_cells_test() {
+        // nothing to merge
+        let mesh = grid(4);
+        let decimated = decimate(&mesh, 0.01);
+
+        assert_eq!(decimated.len(), mesh.len());
+        assert_eq!(decimated[5].vertices, mesh[5].vertices);
+    }
+}
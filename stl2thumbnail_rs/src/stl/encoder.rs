@@ -21,7 +21,7 @@ pub fn encode_gif<P: AsRef<Path>>(path: P, pictures: &[Picture]) -> Result<()> {
         })
         .collect();
 
-    encoder.encode_frames(animation_frames.into_iter())?;
+    encoder.encode_frames(animation_frames)?;
 
     Ok(())
 }
@@ -1,7 +1,8 @@
-use super::parser::Parser;
-use std::cell::RefCell;
+use super::parser::{ParseWarning, Parser};
+use anyhow::Result;
 use std::io::{Read, Seek};
 use std::ops::Index;
+use std::sync::{Mutex, MutexGuard, PoisonError};
 
 // glm aliases
 pub type Mat4 = glm::Mat4x4;
@@ -27,18 +28,99 @@ impl Triangle {
     }
 }
 
+// Facet
+/// A triangle and its attributes, meshes keep the attributes in side tables
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Facet {
+    pub triangle: Triangle,
+    /// per vertex colors (rgb, 0.0-1.0), rendered instead of the model color
+    pub colors: Option<[Vec3; 3]>,
+    /// index of the solid (ascii stl) the triangle belongs to
+    pub solid: u32,
+}
+
+impl Facet {
+    pub fn with_colors(mut self, colors: [Vec3; 3]) -> Self {
+        self.colors = Some(colors);
+        self
+    }
+}
+
+impl From<Triangle> for Facet {
+    fn from(triangle: Triangle) -> Self {
+        Self {
+            triangle,
+            colors: None,
+            solid: 0,
+        }
+    }
+}
+
 // Mesh
+#[derive(Default)]
 pub struct Mesh {
     triangles: Vec<Triangle>,
+    // side tables indexed like the triangles, empty as long as no triangle has the attribute
+    colors: Vec<Option<[Vec3; 3]>>,
+    solids: Vec<u32>,
+    solid_names: Vec<String>,
 }
 
 impl Mesh {
     pub fn new(triangles: Vec<Triangle>) -> Self {
-        Self { triangles }
+        Self {
+            triangles,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_solid_names(mut self, solid_names: Vec<String>) -> Self {
+        self.solid_names = solid_names;
+        self
+    }
+
+    /// Per vertex colors of every triangle, missing colors are `None` and surplus ones are dropped
+    pub fn with_colors(mut self, mut colors: Vec<Option<[Vec3; 3]>>) -> Self {
+        colors.resize(self.triangles.len(), None);
+        self.colors = colors;
+        self
+    }
+
+    pub fn push(&mut self, facet: Facet) {
+        // the side tables are filled up once the first triangle has the attribute
+        if facet.colors.is_some() || !self.colors.is_empty() {
+            self.colors.resize(self.triangles.len(), None);
+            self.colors.push(facet.colors);
+        }
+
+        if facet.solid != 0 || !self.solids.is_empty() {
+            self.solids.resize(self.triangles.len(), 0);
+            self.solids.push(facet.solid);
+        }
+
+        self.triangles.push(facet.triangle);
+    }
+
+    /// The triangle at `index` with its attributes
+    pub fn facet(&self, index: usize) -> Facet {
+        Facet {
+            triangle: self.triangles[index],
+            colors: self.colors.get(index).copied().flatten(),
+            solid: self.solids.get(index).copied().unwrap_or_default(),
+        }
+    }
+
+    /// See `repair::repair_orientation`
+    pub fn repair_orientation(&mut self) {
+        super::repair::repair_orientation(&mut self.triangles, &mut self.colors);
     }
 }
 
 impl Mesh {
+    pub fn solid_name(&self, facet: &Facet) -> Option<&str> {
+        self.solid_names.get(facet.solid as usize).map(String::as_str)
+    }
+
     pub fn len(&self) -> usize {
         self.triangles.len()
     }
@@ -55,67 +137,257 @@ impl Index<usize> for Mesh {
     }
 }
 
+impl FromIterator<Facet> for Mesh {
+    fn from_iter<I: IntoIterator<Item = Facet>>(iter: I) -> Self {
+        let mut mesh = Self::default();
+        for facet in iter {
+            mesh.push(facet);
+        }
+        mesh
+    }
+}
+
 pub struct MeshIter<'a> {
-    mesh: &'a [Triangle],
+    mesh: &'a Mesh,
     i: usize,
 }
 
 impl<'a> IntoIterator for &'a Mesh {
-    type Item = Triangle;
+    type Item = Facet;
     type IntoIter = MeshIter<'a>;
 
     fn into_iter(self) -> Self::IntoIter {
-        Self::IntoIter {
-            mesh: &self.triangles,
-            i: 0,
-        }
+        Self::IntoIter { mesh: self, i: 0 }
     }
 }
 
 impl<'a> Iterator for MeshIter<'a> {
-    type Item = Triangle;
+    type Item = Facet;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let triangle = self.mesh.get(self.i).copied();
+        let facet = (self.i < self.mesh.len()).then(|| self.mesh.facet(self.i));
         self.i += 1;
-        triangle
+        facet
+    }
+}
+
+// TransformedMesh
+/// Applies a transform to the triangles of a mesh while iterating
+#[derive(Clone, Copy)]
+pub struct TransformedMesh<M> {
+    mesh: M,
+    transform: Mat4,
+}
+
+impl<M: IntoIterator<Item = Facet>> TransformedMesh<M> {
+    pub fn new(mesh: M, transform: Mat4) -> Self {
+        Self { mesh, transform }
+    }
+}
+
+impl<M: IntoIterator<Item = Facet>> IntoIterator for TransformedMesh<M> {
+    type Item = Facet;
+    type IntoIter = TransformedMeshIter<M::IntoIter>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Self::IntoIter {
+            inner: self.mesh.into_iter(),
+            transform: self.transform,
+            normal_transform: glm::transpose(&glm::inverse(&self.transform)),
+        }
+    }
+}
+
+pub struct TransformedMeshIter<I> {
+    inner: I,
+    transform: Mat4,
+    normal_transform: Mat4,
+}
+
+impl<I: Iterator<Item = Facet>> Iterator for TransformedMeshIter<I> {
+    type Item = Facet;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut facet = self.inner.next()?;
+
+        // the usual case, saves the matrix multiplications
+        if self.transform == Mat4::identity() {
+            return Some(facet);
+        }
+
+        let triangle = &mut facet.triangle;
+        triangle.vertices = triangle.vertices.map(|v| transform_point(&self.transform, &v));
+        triangle.normal = (self.normal_transform * glm::vec3_to_vec4(&triangle.normal)).xyz();
+
+        Some(facet)
     }
 }
 
 // LazyMesh
-pub struct LazyMesh<'a, T: Read + Seek> {
-    parser: RefCell<&'a mut Parser<T>>, // inner mutability
+/// A low memory mesh, every pass reads the triangles from a freshly opened parser
+///
+/// Passes are independent and can run concurrently. Errors are collected and reported by `check`.
+pub struct LazyMesh<F, T: Read + Seek> {
+    open: F,
+    opened: Mutex<Option<Parser<T>>>, // opened by `try_new`, reused by the next pass
+    error: Mutex<Option<anyhow::Error>>,
+    last_pass: Mutex<Option<(u64, Vec<ParseWarning>)>>, // triangles read, warnings
 }
 
-impl<'a, T> LazyMesh<'a, T>
+impl<F, T> LazyMesh<F, T>
 where
+    F: Fn() -> Result<Parser<T>>,
     T: Read + Seek,
 {
-    pub fn new(parser: &'a mut Parser<T>) -> Self {
+    /// `open` is called at the start of every pass, e.g. `|| Parser::from_file(path, false)`
+    pub fn new(open: F) -> Self {
         Self {
-            parser: RefCell::new(parser),
+            open,
+            opened: Mutex::new(None),
+            error: Mutex::new(None),
+            last_pass: Mutex::new(None),
         }
     }
+
+    /// Opens the parser right away to fail early, the first pass continues with it
+    pub fn try_new(open: F) -> Result<Self> {
+        let parser = open()?;
+
+        let mesh = Self::new(open);
+        *lock(&mesh.opened) = Some(parser);
+        Ok(mesh)
+    }
+
+    /// Returns the first error that occurred while opening or reading the file
+    pub fn check(&self) -> Result<()> {
+        match lock(&self.error).take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Number of triangles read by the last complete pass
+    pub fn triangles_read(&self) -> u64 {
+        lock(&self.last_pass).as_ref().map_or(0, |(count, _)| *count)
+    }
+
+    /// Warnings of the last complete pass (tolerant mode only)
+    pub fn warnings(&self) -> Vec<ParseWarning> {
+        lock(&self.last_pass)
+            .as_ref()
+            .map(|(_, warnings)| warnings.clone())
+            .unwrap_or_default()
+    }
 }
 
-pub struct LazyMeshIter<'a, T: Read + Seek> {
-    parser: &'a RefCell<&'a mut Parser<T>>,
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // the data stays consistent even if a pass panicked
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
 }
 
-impl<'a, T: Read + Seek> IntoIterator for &'a LazyMesh<'a, T> {
-    type Item = Triangle;
-    type IntoIter = LazyMeshIter<'a, T>;
+pub struct LazyMeshIter<'a, F, T: Read + Seek> {
+    mesh: &'a LazyMesh<F, T>,
+    parser: Option<Parser<T>>,
+}
+
+impl<'a, F, T> IntoIterator for &'a LazyMesh<F, T>
+where
+    F: Fn() -> Result<Parser<T>>,
+    T: Read + Seek,
+{
+    type Item = Facet;
+    type IntoIter = LazyMeshIter<'a, F, T>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.parser.borrow_mut().rewind().unwrap();
-        Self::IntoIter { parser: &self.parser }
+        let opened = lock(&self.opened).take();
+        let parser = match opened.map_or_else(&self.open, Ok) {
+            Ok(parser) => Some(parser),
+            Err(err) => {
+                lock(&self.error).get_or_insert(err);
+                None
+            }
+        };
+
+        Self::IntoIter { mesh: self, parser }
     }
 }
 
-impl<'a, T: Read + Seek> Iterator for LazyMeshIter<'a, T> {
-    type Item = Triangle;
+impl<F, T: Read + Seek> Iterator for LazyMeshIter<'_, F, T> {
+    type Item = Facet;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.parser.borrow_mut().next_triangle()
+        let parser = self.parser.as_mut()?;
+
+        match parser.try_next_triangle() {
+            Ok(Some(facet)) => return Some(facet),
+            Ok(None) => *lock(&self.mesh.last_pass) = Some((parser.triangles_read(), parser.warnings().to_vec())),
+            Err(err) => {
+                lock(&self.mesh.error).get_or_insert(err);
+            }
+        }
+
+        self.parser = None;
+        None
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    /// An axis aligned box from `offset` to `offset + size`, wound counterclockwise seen from outside
+    ///
+    /// The normals are left at zero.
+    pub(crate) fn cuboid(offset: Vec3, size: Vec3) -> Vec<Triangle> {
+        let v = |x: f32, y: f32, z: f32| offset + Vec3::new(x, y, z).component_mul(&size);
+        let quads = [
+            [v(0., 0., 0.), v(0., 1., 0.), v(1., 1., 0.), v(1., 0., 0.)], // bottom
+            [v(0., 0., 1.), v(1., 0., 1.), v(1., 1., 1.), v(0., 1., 1.)], // top
+            [v(0., 0., 0.), v(1., 0., 0.), v(1., 0., 1.), v(0., 0., 1.)], // front
+            [v(0., 1., 0.), v(0., 1., 1.), v(1., 1., 1.), v(1., 1., 0.)], // back
+            [v(0., 0., 0.), v(0., 0., 1.), v(0., 1., 1.), v(0., 1., 0.)], // left
+            [v(1., 0., 0.), v(1., 1., 0.), v(1., 1., 1.), v(1., 0., 1.)], // right
+        ];
+
+        quads
+            .iter()
+            .flat_map(|q| [[q[0], q[1], q[2]], [q[0], q[2], q[3]]])
+            .map(|vertices| Triangle::new(vertices, Vec3::zeros()))
+            .collect()
+    }
+
+    #[test]
+    fn mesh_side_tables_test() {
+        // the attributes do not bloat the triangles
+        assert_eq!(std::mem::size_of::<Triangle>(), 48);
+
+        let triangle = Triangle::new([Vec3::zeros(); 3], Vec3::new(0.0, 0.0, 1.0));
+        let red = [Vec3::new(1.0, 0.0, 0.0); 3];
+
+        let mut mesh = Mesh::default();
+        mesh.push(triangle.into());
+        assert!(mesh.colors.is_empty() && mesh.solids.is_empty());
+
+        mesh.push(Facet::from(triangle).with_colors(red));
+        mesh.push(Facet {
+            solid: 2,
+            ..triangle.into()
+        });
+
+        assert_eq!(mesh.len(), 3);
+        assert_eq!(mesh.facet(0), Facet::from(triangle));
+        assert_eq!(mesh.facet(1).colors, Some(red));
+        assert_eq!(mesh.facet(1).solid, 0);
+        assert_eq!(mesh.facet(2).colors, None);
+        assert_eq!(mesh.facet(2).solid, 2);
+
+        let facets: Vec<Facet> = (&mesh).into_iter().collect();
+        assert_eq!(facets, [0, 1, 2].map(|i| mesh.facet(i)));
+
+        // mismatching color tables are padded or truncated
+        let mesh = Mesh::new(vec![triangle; 2]).with_colors(vec![Some(red)]);
+        assert_eq!(mesh.facet(1).colors, None);
+        let mesh = Mesh::new(vec![triangle; 2]).with_colors(vec![Some(red); 3]);
+        assert_eq!(mesh.colors.len(), 2);
     }
 }
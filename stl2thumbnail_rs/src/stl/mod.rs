@@ -1,41 +1,87 @@
 pub mod aabb;
+pub mod analysis;
+pub mod decimate;
 pub mod encoder;
 pub mod mesh;
+pub mod orientation;
 pub mod parser;
 pub mod rasterbackend;
+pub mod repair;
+pub mod shadow;
 pub mod zbuffer;
 
-use std::path::Path;
+use std::{path::Path, time::Instant};
 
 use self::{
+    aabb::AABB,
+    analysis::MaterialEstimate,
     encoder::encode_gif,
-    mesh::{Triangle, Vec3},
+    mesh::{Facet, Mesh, TransformedMesh, Vec3},
     rasterbackend::RasterBackend,
 };
 use crate::{picture::Picture, Settings};
 use anyhow::Result;
 
-pub fn render_stl<P: AsRef<Path>>(
+/// Outcome of a render, frames that hit the timeout only show the coarse preview (incomplete in lazy mode)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderSummary {
+    pub frames: usize,
+    pub coarse_frames: usize,
+}
+
+/// A still or the frames of a turntable animation
+pub struct Rendering {
+    pub pictures: Vec<Picture>,
+    pub summary: RenderSummary,
+}
+
+impl Rendering {
+    /// Saves a still as picture and an animation as gif
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        match self.pictures.as_slice() {
+            [picture] => picture.save(path),
+            pictures => encode_gif(path, pictures),
+        }
+    }
+}
+
+/// Renders the mesh, saving is left to the caller, e.g. to check a lazy mesh for read errors first
+pub fn render_stl(
+    width: u32,
+    height: u32,
+    mesh: impl IntoIterator<Item = Facet> + Copy,
+    settings: &Settings,
+) -> Result<Rendering> {
+    let transform = orientation::orientation_transform(mesh, settings.orientation);
+    let mesh = TransformedMesh::new(mesh, transform);
+
+    // triangles smaller than a pixel only cost time
+    if let Some(decimated) = decimate_for_preview(mesh, width, height, settings) {
+        return Ok(render_prepared(width, height, &decimated, settings));
+    }
+
+    Ok(render_prepared(width, height, mesh, settings))
+}
+
+fn render_prepared(
     width: u32,
     height: u32,
-    mesh: impl IntoIterator<Item = Triangle> + Copy,
-    path: P,
+    mesh: impl IntoIterator<Item = Facet> + Copy,
     settings: &Settings,
-) -> Result<()> {
+) -> Rendering {
     if settings.turntable {
-        render_stl_turntable_animation(width, height, mesh, path, settings)
+        render_stl_turntable_animation(width, height, mesh, settings)
     } else {
-        render_stl_still(width, height, mesh, path, settings)
+        render_stl_still(width, height, mesh, settings)
     }
 }
 
-pub fn render_stl_still<P: AsRef<Path>>(
+pub fn render_stl_still(
     width: u32,
     height: u32,
-    mesh: impl IntoIterator<Item = Triangle> + Copy,
-    path: P,
+    mesh: impl IntoIterator<Item = Facet> + Copy,
     settings: &Settings,
-) -> Result<()> {
+) -> Rendering {
     let mut backend = RasterBackend::new(width, height);
     backend.render_options.grid_visible = settings.grid;
 
@@ -48,20 +94,30 @@ pub fn render_stl_still<P: AsRef<Path>>(
     let (aabb, scale) = backend.fit_mesh_scale(mesh);
     backend.render_options.zoom = 1.05;
     backend.render_options.draw_size_hint = settings.size_hint;
+    backend.render_options.color_solids = settings.color_solids;
+    backend.render_options.two_sided = settings.two_sided;
+    backend.render_options.shadow_opacity = settings.shadow;
+    backend.render_options.material_estimate = material_estimate(mesh, settings);
     backend.render_options.background_color = settings.background_color.as_vec4();
+    backend.render_options.coarse_proxy = !settings.lazy;
 
-    backend.render(mesh, scale, &aabb, settings.timeout).save(path)?;
+    let rendering = backend.render(mesh, scale, &aabb, settings.timeout);
 
-    Ok(())
+    Rendering {
+        summary: RenderSummary {
+            frames: 1,
+            coarse_frames: usize::from(!rendering.is_final),
+        },
+        pictures: vec![rendering.picture],
+    }
 }
 
-pub fn render_stl_turntable_animation<P: AsRef<Path>>(
+pub fn render_stl_turntable_animation(
     width: u32,
     height: u32,
-    mesh: impl IntoIterator<Item = Triangle> + Copy,
-    path: P,
+    mesh: impl IntoIterator<Item = Facet> + Copy,
     settings: &Settings,
-) -> Result<()> {
+) -> Rendering {
     let mut backend = RasterBackend::new(width, height);
     backend.render_options.grid_visible = settings.grid;
     let mut pictures: Vec<Picture> = Vec::new();
@@ -70,15 +126,74 @@ pub fn render_stl_turntable_animation<P: AsRef<Path>>(
     let (aabb, scale) = backend.fit_mesh_scale(mesh);
     backend.render_options.zoom = 1.05;
     backend.render_options.draw_size_hint = settings.size_hint;
+    backend.render_options.color_solids = settings.color_solids;
+    backend.render_options.two_sided = settings.two_sided;
+    backend.render_options.shadow_opacity = settings.shadow;
+    backend.render_options.material_estimate = material_estimate(mesh, settings);
+    backend.render_options.coarse_proxy = !settings.lazy;
+
+    // the proxy does not depend on the view, building it counts against the first frame
+    let mut frame_start = Instant::now();
+    let proxy = settings
+        .timeout
+        .filter(|_| !settings.lazy)
+        .map(|timeout| (RasterBackend::coarse_proxy(mesh, &aabb), timeout));
+    let mut coarse_frames = 0;
 
     for i in 0..45 {
         let angle = (8.0 * i as f32).to_radians();
         backend.render_options.view_pos =
             Vec3::new(angle.cos(), angle.sin(), -settings.cam_elevation.to_radians().tan());
-        pictures.push(backend.render(mesh, scale, &aabb, settings.timeout));
+
+        let rendering = match &proxy {
+            Some((proxy, timeout)) => backend.render_progressive(mesh, proxy, scale, &aabb, frame_start + *timeout),
+            None => backend.render(mesh, scale, &aabb, settings.timeout),
+        };
+        frame_start = Instant::now();
+        if !rendering.is_final {
+            coarse_frames += 1;
+        }
+        pictures.push(rendering.picture);
+    }
+
+    Rendering {
+        summary: RenderSummary {
+            frames: pictures.len(),
+            coarse_frames,
+        },
+        pictures,
+    }
+}
+
+/// The estimate shown in the size hint (costs an additional pass over the mesh)
+fn material_estimate(mesh: impl IntoIterator<Item = Facet> + Copy, settings: &Settings) -> Option<MaterialEstimate> {
+    let material = settings.material.as_ref().filter(|_| settings.size_hint)?;
+    let (volume, surface_area) = analysis::volume_and_area(mesh);
+    Some(analysis::estimate_material(volume, surface_area, material))
+}
+
+/// Vertex clustering on a grid of about a pixel if the mesh has more triangles than `settings.decimate_above`
+///
+/// Not in lazy mode, the clustering takes memory proportional to the mesh.
+fn decimate_for_preview(
+    mesh: impl IntoIterator<Item = Facet> + Copy,
+    width: u32,
+    height: u32,
+    settings: &Settings,
+) -> Option<Mesh> {
+    let threshold = settings.decimate_above.filter(|_| !settings.lazy)?;
+
+    let mut count = 0;
+    let aabb = AABB::from_iterable(mesh.into_iter().inspect(|_| count += 1));
+
+    // there is little to merge if the triangles are about pixel sized already
+    if count <= threshold.max(4 * width as usize * height as usize) {
+        return None;
     }
 
-    encode_gif(path, pictures.as_slice())?;
+    // the model is scaled to fit the picture
+    let extent = aabb.size().max();
+    let cell_size = extent / width.max(height) as f32;
 
-    Ok(())
+    Some(decimate::decimate(mesh, cell_size))
 }
@@ -0,0 +1,316 @@
+use std::collections::{HashMap, HashSet};
+
+use super::mesh::{Facet, Mat4, Vec3};
+
+type DVec3 = glm::DVec3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Orientation {
+    /// the model is rendered as it is
+    #[default]
+    ZUp,
+    /// models exported by Blender, game engines, ...
+    ///
+    /// Not for glTF and X3D/VRML, their readers already convert them to Z-up.
+    YUp,
+    /// lays the model on its most stable face
+    Auto,
+}
+
+/// Rotation which brings the mesh into a Z-up orientation
+pub fn orientation_transform(mesh: impl IntoIterator<Item = Facet> + Copy, orientation: Orientation) -> Mat4 {
+    match orientation {
+        Orientation::ZUp => Mat4::identity(),
+        Orientation::YUp => glm::rotation(90.0f32.to_radians(), &Vec3::new(1.0, 0.0, 0.0)),
+        Orientation::Auto => match stable_face_normal(mesh) {
+            Some(normal) => rotation_between(&normal, &Vec3::new(0.0, 0.0, -1.0)),
+            None => Mat4::identity(),
+        },
+    }
+}
+
+/// Outwards pointing normal of the convex hull face the model rests on most stably
+///
+/// A face is stable if the center of mass projects onto it, the largest stable face wins.
+fn stable_face_normal(mesh: impl IntoIterator<Item = Facet> + Copy) -> Option<Vec3> {
+    let center_of_mass = center_of_mass(mesh)?;
+
+    // the hull only depends on the unique vertices
+    let mut unique = HashSet::new();
+    let points: Vec<DVec3> = mesh
+        .into_iter()
+        .flat_map(|f| f.triangle.vertices)
+        .filter(|v| unique.insert([v.x, v.y, v.z].map(|c| (c + 0.0).to_bits())))
+        .map(|v| v.map(|c| c as f64))
+        .collect();
+
+    let hull = convex_hull(&points)?;
+
+    // merge the coplanar hull triangles to faces
+    let mut faces: Vec<(DVec3, f64, Vec<[DVec3; 3]>)> = vec![]; // normal, area, triangles
+    for [a, b, c] in hull.iter().map(|f| f.map(|i| points[i])) {
+        let cross = (b - a).cross(&(c - a));
+        let normal = cross.normalize();
+
+        let triangle = [a, b, c];
+        match faces
+            .iter_mut()
+            .find(|(n, _, t)| n.dot(&normal) > 1.0 - 1e-9 && (a - t[0][0]).dot(n).abs() < 1e-6 * (1.0 + a.norm()))
+        {
+            Some((_, area, triangles)) => {
+                *area += cross.norm() / 2.0;
+                triangles.push(triangle);
+            }
+            None => faces.push((normal, cross.norm() / 2.0, vec![triangle])),
+        }
+    }
+
+    faces
+        .into_iter()
+        .filter(|(normal, _, triangles)| {
+            // project the center of mass onto the face plane
+            let p = center_of_mass - normal * (center_of_mass - triangles[0][0]).dot(normal);
+            triangles.iter().any(|t| contains(t, normal, &p))
+        })
+        .max_by(|(_, a, _), (_, b, _)| a.total_cmp(b))
+        .map(|(normal, _, _)| normal.map(|c| c as f32))
+}
+
+/// Volume centroid, or the area weighted centroid if the mesh is not closed
+fn center_of_mass(mesh: impl IntoIterator<Item = Facet> + Copy) -> Option<DVec3> {
+    let mut volume = 0.0;
+    let mut volume_centroid = DVec3::zeros();
+    let mut area = 0.0;
+    let mut area_centroid = DVec3::zeros();
+
+    for Facet { triangle: t, .. } in mesh {
+        let [a, b, c] = t.vertices.map(|v| v.map(|c| c as f64));
+
+        let v = a.dot(&b.cross(&c)) / 6.0;
+        volume += v;
+        volume_centroid += (a + b + c) / 4.0 * v;
+
+        let s = (b - a).cross(&(c - a)).norm() / 2.0;
+        area += s;
+        area_centroid += (a + b + c) / 3.0 * s;
+    }
+
+    if volume.abs() > 1e-9 * area.powf(1.5) {
+        Some(volume_centroid / volume)
+    } else if area > 0.0 {
+        Some(area_centroid / area)
+    } else {
+        None
+    }
+}
+
+fn contains(t: &[DVec3; 3], normal: &DVec3, p: &DVec3) -> bool {
+    let eps = -1e-9 * (t[1] - t[0]).norm_squared();
+    (0..3).all(|i| (t[(i + 1) % 3] - t[i]).cross(&(p - t[i])).dot(normal) >= eps)
+}
+
+fn rotation_between(from: &Vec3, to: &Vec3) -> Mat4 {
+    let axis = from.cross(to);
+    let angle = from.dot(to).clamp(-1.0, 1.0).acos();
+
+    if axis.norm() > 1e-6 {
+        glm::rotation(angle, &axis.normalize())
+    } else if from.dot(to) > 0.0 {
+        Mat4::identity()
+    } else {
+        glm::rotation(std::f32::consts::PI, &Vec3::new(1.0, 0.0, 0.0))
+    }
+}
+
+struct Face {
+    vertices: [usize; 3],
+    normal: DVec3,
+    offset: f64,
+    outside: Vec<usize>,
+    alive: bool,
+}
+
+impl Face {
+    fn new(vertices: [usize; 3], points: &[DVec3]) -> Self {
+        let [a, b, c] = vertices.map(|i| points[i]);
+        let normal = (b - a).cross(&(c - a)).normalize();
+
+        Self {
+            vertices,
+            normal,
+            offset: normal.dot(&a),
+            outside: vec![],
+            alive: true,
+        }
+    }
+
+    fn distance(&self, p: &DVec3) -> f64 {
+        self.normal.dot(p) - self.offset
+    }
+}
+
+/// Quickhull, returns the outwards oriented hull triangles or `None` if the points are coplanar
+fn convex_hull(points: &[DVec3]) -> Option<Vec<[usize; 3]>> {
+    let extent = points.iter().fold(0.0f64, |m, p| m.max(p.amax()));
+    let eps = 1e-9 * extent.max(1.0);
+
+    // initial tetrahedron
+    let farthest =
+        |f: &dyn Fn(&DVec3) -> f64| (0..points.len()).max_by(|&a, &b| f(&points[a]).total_cmp(&f(&points[b])));
+
+    let i0 = farthest(&|p| -p.x)?;
+    let i1 = farthest(&|p| (p - points[i0]).norm())?;
+    let line = (points[i1] - points[i0]).normalize();
+    let i2 = farthest(&|p| (p - points[i0]).cross(&line).norm())?;
+    let plane = (points[i1] - points[i0]).cross(&(points[i2] - points[i0])).normalize();
+    let i3 = farthest(&|p| (p - points[i0]).dot(&plane).abs())?;
+
+    if (points[i3] - points[i0]).dot(&plane).abs() <= eps || !plane.iter().all(|c| c.is_finite()) {
+        return None;
+    }
+
+    let interior = (points[i0] + points[i1] + points[i2] + points[i3]) / 4.0;
+
+    let mut faces: Vec<Face> = vec![];
+    for mut vertices in [[i0, i1, i2], [i0, i1, i3], [i0, i2, i3], [i1, i2, i3]] {
+        if Face::new(vertices, points).distance(&interior) > 0.0 {
+            vertices.swap(0, 1);
+        }
+        faces.push(Face::new(vertices, points));
+    }
+
+    // directed edge -> face, the neighbor across the edge (a, b) owns the edge (b, a)
+    let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+    for (i, face) in faces.iter().enumerate() {
+        add_edges(&mut edges, face, i);
+    }
+
+    assign_outside(&mut faces, 0, (0..points.len()).collect(), points, eps);
+    let mut pending: Vec<usize> = (0..faces.len()).collect();
+
+    while let Some(index) = pending.pop() {
+        let face = &faces[index];
+        if !face.alive || face.outside.is_empty() {
+            continue;
+        }
+
+        let eye = *face
+            .outside
+            .iter()
+            .max_by(|&&a, &&b| face.distance(&points[a]).total_cmp(&face.distance(&points[b])))?;
+
+        // the faces seen from the eye point are connected, the horizon is their boundary
+        let mut visible = vec![index];
+        let mut horizon = vec![];
+        faces[index].alive = false;
+
+        let mut i = 0;
+        while i < visible.len() {
+            let [a, b, c] = faces[visible[i]].vertices;
+            for (a, b) in [(a, b), (b, c), (c, a)] {
+                let Some(&neighbor) = edges.get(&(b, a)) else {
+                    continue;
+                };
+
+                if faces[neighbor].alive && faces[neighbor].distance(&points[eye]) > eps {
+                    faces[neighbor].alive = false;
+                    visible.push(neighbor);
+                } else if faces[neighbor].alive {
+                    horizon.push((a, b));
+                }
+            }
+            i += 1;
+        }
+
+        let mut orphans = vec![];
+        for &i in &visible {
+            remove_edges(&mut edges, &faces[i]);
+            orphans.append(&mut faces[i].outside);
+        }
+
+        // replace the visible faces by a cone from the horizon to the eye point
+        let first_new = faces.len();
+        for (a, b) in horizon {
+            let face = Face::new([a, b, eye], points);
+            add_edges(&mut edges, &face, faces.len());
+            faces.push(face);
+        }
+
+        orphans.retain(|&p| p != eye);
+        assign_outside(&mut faces, first_new, orphans, points, eps);
+        pending.extend(first_new..faces.len());
+    }
+
+    Some(faces.iter().filter(|f| f.alive).map(|f| f.vertices).collect())
+}
+
+fn add_edges(edges: &mut HashMap<(usize, usize), usize>, face: &Face, index: usize) {
+    let [a, b, c] = face.vertices;
+    for edge in [(a, b), (b, c), (c, a)] {
+        edges.insert(edge, index);
+    }
+}
+
+fn remove_edges(edges: &mut HashMap<(usize, usize), usize>, face: &Face) {
+    let [a, b, c] = face.vertices;
+    for edge in [(a, b), (b, c), (c, a)] {
+        edges.remove(&edge);
+    }
+}
+
+fn assign_outside(faces: &mut [Face], first: usize, candidates: Vec<usize>, points: &[DVec3], eps: f64) {
+    for p in candidates {
+        if let Some(face) = faces[first..]
+            .iter_mut()
+            .find(|f| f.alive && f.distance(&points[p]) > eps)
+        {
+            face.outside.push(p);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::stl::mesh::{test::cuboid, transform_point, Mesh};
+
+    #[test]
+    fn convex_hull_test() {
+        let mut points: Vec<DVec3> = (0..8)
+            .map(|i| DVec3::new((i & 1) as f64, (i >> 1 & 1) as f64, (i >> 2 & 1) as f64))
+            .collect();
+        points.push(DVec3::new(0.5, 0.5, 0.5)); // inside
+
+        let hull = convex_hull(&points).unwrap();
+        assert_eq!(hull.len(), 12);
+        assert!(hull.iter().all(|f| !f.contains(&8)));
+
+        // coplanar
+        assert!(convex_hull(&points[..4]).is_none());
+    }
+
+    #[test]
+    fn orientation_y_up_test() {
+        let transform = orientation_transform(&Mesh::new(cuboid(Vec3::zeros(), Vec3::repeat(1.0))), Orientation::YUp);
+        let v = transform_point(&transform, &Vec3::new(0.0, 1.0, 0.0));
+
+        assert!((v - Vec3::new(0.0, 0.0, 1.0)).norm() < 1e-6);
+    }
+
+    #[test]
+    fn orientation_auto_test() {
+        // a plate standing on its narrow side lays flat
+        let mesh = Mesh::new(cuboid(Vec3::zeros(), Vec3::new(10.0, 1.0, 5.0)));
+        let transform = orientation_transform(&mesh, Orientation::Auto);
+
+        let heights: Vec<f32> = (&mesh)
+            .into_iter()
+            .flat_map(|f| f.triangle.vertices)
+            .map(|v| transform_point(&transform, &v).z)
+            .collect();
+        let min = heights.iter().copied().fold(f32::MAX, f32::min);
+        let max = heights.iter().copied().fold(f32::MIN, f32::max);
+
+        assert!((max - min - 1.0).abs() < 1e-4);
+    }
+}
@@ -2,14 +2,19 @@ use super::mesh::*;
 use anyhow::bail;
 use anyhow::Result;
 use byteorder::{LittleEndian, ReadBytesExt};
+use flate2::read::MultiGzDecoder;
 use scan_fmt::scan_fmt;
+use std::fmt;
 use std::fs;
 use std::io;
-use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::io::{BufRead, BufReader, Cursor, Read, Seek, SeekFrom};
 use std::path::Path;
+use std::thread;
 
 const HEADER_SIZE: u64 = 80;
 const TRIANGLE_SIZE: u64 = 50;
+const BULK_CHUNK_SIZE: usize = 1 << 20; // triangles, 50MB
+const MIN_TRIANGLES_PER_THREAD: usize = 1 << 12;
 
 #[derive(Debug, Clone, Copy)]
 pub enum StlType {
@@ -17,6 +22,46 @@ pub enum StlType {
     Ascii,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParseMode {
+    /// stops at the first malformed triangle
+    #[default]
+    Strict,
+    /// skips malformed data and reports it as warnings
+    Tolerant,
+}
+
+/// Data skipped by the tolerant parser
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseWarning {
+    /// position of the skipped bytes in the (decompressed) file
+    pub offset: u64,
+    /// number of skipped bytes
+    pub length: u64,
+    pub message: String,
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "skipped {} bytes at offset {}: {}",
+            self.length, self.offset, self.message
+        )
+    }
+}
+
+/// Conventions for the facet colors stored in the attribute word of binary stl files
+///
+/// ref: https://en.wikipedia.org/wiki/STL_(file_format)#Colour_in_binary_STL
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorConvention {
+    /// bit 15 set: the facet has a color, BGR555
+    VisCam,
+    /// bit 15 cleared: the facet has a color, RGB555, otherwise the default color ('COLOR=' or 'MATERIAL=' in the header)
+    Magics { default_color: Option<Vec3> },
+}
+
 pub struct Parser<T>
 where
     T: Read + Seek,
@@ -25,27 +70,77 @@ where
     stl_type: StlType,
     header_length: u64,
     recalculate_normals: bool,
+    solid_names: Vec<String>,
+    current_solid: u32,
+    color_convention: ColorConvention,
+    mode: ParseMode,
+    binary_triangle_count: u64,
+    binary_index: u64,
+    ascii_offset: u64, // position of the next line (tolerant mode)
+    triangles_read: u64,
+    warnings: Vec<ParseWarning>,
+    header_warnings: usize, // warnings that are not reset by rewind
 }
 
 impl<T: Read + Seek> Parser<T> {
     pub fn from_buf(inner: T, recalculate_normals: bool) -> Result<Self> {
+        Self::from_buf_with_mode(inner, recalculate_normals, ParseMode::Strict)
+    }
+
+    pub fn from_buf_with_mode(inner: T, recalculate_normals: bool, mode: ParseMode) -> Result<Self> {
         let mut reader = BufReader::new(inner);
 
-        let stl_type = deduce_stl_type(&mut reader)?;
+        let stl_type = deduce_stl_type(&mut reader, mode)?;
+        let filesize = reader.seek(SeekFrom::End(0))?;
         reader.rewind()?;
 
         // figure out header size
         let mut header_length = 0;
+        let mut solid_names = vec![];
+        let mut color_convention = ColorConvention::VisCam;
+        let mut binary_triangle_count = 0;
+        let mut warnings = vec![];
         match stl_type {
             StlType::Binary => {
                 header_length = HEADER_SIZE + 4; // header size + triangle count (u32)
+
+                let mut header = [0u8; HEADER_SIZE as usize];
+                reader.read_exact(&mut header)?;
+                color_convention = deduce_color_convention(&header);
+
+                // in tolerant mode the count does not have to match the file size
+                let count = reader.read_u32::<LittleEndian>()? as u64;
+                let available = (filesize - header_length) / TRIANGLE_SIZE;
+
+                binary_triangle_count = match count {
+                    0 => available,
+                    _ => count.min(available),
+                };
+
+                let end = header_length + binary_triangle_count * TRIANGLE_SIZE;
+                if count > available {
+                    warnings.push(ParseWarning {
+                        offset: end,
+                        length: filesize - end,
+                        message: format!("file truncated, expected {count} triangles but found {available}"),
+                    });
+                } else if end < filesize {
+                    warnings.push(ParseWarning {
+                        offset: end,
+                        length: filesize - end,
+                        message: "trailing data".to_string(),
+                    });
+                }
             }
             StlType::Ascii => {
-                while let Ok(line) = read_ascii_line(&mut reader) {
-                    if line.starts_with("solid") {
+                let mut line = String::new();
+                while reader.read_line(&mut line)? > 0 {
+                    if let Some(name) = solid_name(&line) {
                         header_length = reader.stream_position()?;
+                        solid_names.push(name);
                         break;
                     }
+                    line.clear();
                 }
             }
         };
@@ -55,33 +150,210 @@ impl<T: Read + Seek> Parser<T> {
             stl_type,
             header_length,
             recalculate_normals,
+            solid_names,
+            current_solid: 0,
+            color_convention,
+            mode,
+            binary_triangle_count,
+            binary_index: 0,
+            ascii_offset: header_length,
+            triangles_read: 0,
+            header_warnings: warnings.len(),
+            warnings,
         })
     }
 
     pub fn rewind(&mut self) -> Result<()> {
         self.reader.seek(SeekFrom::Start(self.header_length))?;
+        self.current_solid = 0;
+        self.binary_index = 0;
+        self.ascii_offset = self.header_length;
+        self.triangles_read = 0;
+        self.warnings.truncate(self.header_warnings);
         Ok(())
     }
 
-    pub fn next_triangle(&mut self) -> Option<Triangle> {
-        let mut triangle = match self.stl_type {
-            StlType::Ascii => read_ascii_triangle(&mut self.reader).ok(),
-            StlType::Binary => read_triangle(&mut self.reader).ok(),
+    /// Number of triangles read since the last rewind
+    pub fn triangles_read(&self) -> u64 {
+        self.triangles_read
+    }
+
+    /// Data that was skipped while reading (tolerant mode only)
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    /// Names of the solids of an ascii stl file, see `Facet::solid`
+    ///
+    /// Solids are discovered while parsing.
+    pub fn solid_names(&self) -> &[String] {
+        &self.solid_names
+    }
+
+    pub fn color_convention(&self) -> ColorConvention {
+        self.color_convention
+    }
+
+    /// Returns `None` at the end of the file and at the first error, see `try_next_triangle`
+    pub fn next_triangle(&mut self) -> Option<Facet> {
+        self.try_next_triangle().ok().flatten()
+    }
+
+    /// Reads the next triangle, read errors and malformed data (strict mode) are reported
+    pub fn try_next_triangle(&mut self) -> Result<Option<Facet>> {
+        let mut facet = match (self.stl_type, self.mode) {
+            (StlType::Ascii, ParseMode::Strict) => self.next_ascii_triangle()?,
+            (StlType::Ascii, ParseMode::Tolerant) => self.next_ascii_triangle_tolerant()?,
+            (StlType::Binary, _) => self.next_binary_triangle()?,
         };
 
-        // calculate normal from vertices using right hand rule is case it is missing
-        if let Some(triangle) = &mut triangle {
-            if self.recalculate_normals
-                || triangle.normal == Vec3::new(0.0, 0.0, 0.0)
-                || triangle.normal == Vec3::new(f32::NAN, f32::NAN, f32::NAN)
-            {
-                triangle.normal = (triangle.vertices[1] - triangle.vertices[0])
-                    .cross(&(triangle.vertices[2] - triangle.vertices[0]))
-                    .normalize();
+        if let Some(facet) = &mut facet {
+            fix_normal(&mut facet.triangle, self.recalculate_normals);
+            self.triangles_read += 1;
+        }
+
+        Ok(facet)
+    }
+
+    fn next_binary_triangle(&mut self) -> Result<Option<Facet>> {
+        while self.binary_index < self.binary_triangle_count {
+            let (triangle, attributes) = read_triangle(&mut self.reader)?;
+            self.binary_index += 1;
+
+            if self.mode == ParseMode::Strict || is_finite(&triangle) {
+                let facet = Facet::from(triangle);
+                return Ok(Some(match decode_facet_color(attributes, &self.color_convention) {
+                    Some(color) => facet.with_colors([color; 3]),
+                    None => facet,
+                }));
             }
+
+            let offset = self.header_length + (self.binary_index - 1) * TRIANGLE_SIZE;
+            self.warn(offset, TRIANGLE_SIZE, "invalid vertex coordinates");
         }
 
-        triangle
+        Ok(None)
+    }
+
+    fn next_ascii_triangle_tolerant(&mut self) -> Result<Option<Facet>> {
+        // only the 'facet' and 'vertex' lines matter, this recovers from
+        // extra whitespace, missing normals and missing 'outer loop', 'endloop' or 'endfacet' lines
+        let mut line = String::new();
+        let mut facet: Option<(u64, Vec3, Vec<Vec3>)> = None; // offset, normal, vertices
+
+        loop {
+            let offset = self.ascii_offset;
+
+            line.clear();
+            let length = self.reader.read_line(&mut line)?;
+            self.ascii_offset += length as u64;
+
+            if length == 0 {
+                if let Some((facet_offset, ..)) = facet {
+                    self.warn(facet_offset, offset - facet_offset, "incomplete facet");
+                }
+                return Ok(None);
+            }
+
+            let mut tokens = line.split_ascii_whitespace();
+            let keyword = tokens.next().unwrap_or_default().to_ascii_lowercase();
+
+            match keyword.as_str() {
+                "facet" => {
+                    if let Some((facet_offset, ..)) = facet {
+                        self.warn(facet_offset, offset - facet_offset, "incomplete facet");
+                    }
+
+                    // a missing normal is recalculated
+                    let normal = match tokens.next() {
+                        Some(t) if t.eq_ignore_ascii_case("normal") => parse_vec3(tokens),
+                        _ => None,
+                    };
+
+                    facet = Some((offset, normal.unwrap_or_default(), vec![]));
+                }
+                "vertex" => {
+                    let Some(vertex) = parse_vec3(tokens) else {
+                        self.warn(offset, line.len() as u64, "malformed vertex");
+                        continue;
+                    };
+
+                    let (facet_offset, normal, vertices) = facet.get_or_insert((offset, Vec3::default(), vec![]));
+                    vertices.push(vertex);
+
+                    if vertices.len() == 3 {
+                        let triangle = Triangle::new([vertices[0], vertices[1], vertices[2]], *normal);
+
+                        if is_finite(&triangle) {
+                            return Ok(Some(Facet {
+                                solid: self.current_solid,
+                                ..triangle.into()
+                            }));
+                        }
+
+                        let facet_offset = *facet_offset;
+                        facet = None;
+                        self.warn(
+                            facet_offset,
+                            offset + line.len() as u64 - facet_offset,
+                            "invalid vertex coordinates",
+                        );
+                    }
+                }
+                "solid" => {
+                    self.current_solid += 1;
+                    if self.solid_names.len() <= self.current_solid as usize {
+                        self.solid_names.push(solid_name(&line).unwrap_or_default());
+                    }
+                }
+                "" | "outer" | "endloop" | "endfacet" | "endsolid" => (),
+                _ => self.warn(offset, line.len() as u64, "unknown line"),
+            }
+        }
+    }
+
+    fn warn(&mut self, offset: u64, length: u64, message: &str) {
+        self.warnings.push(ParseWarning {
+            offset,
+            length,
+            message: message.to_string(),
+        });
+    }
+
+    fn next_ascii_triangle(&mut self) -> Result<Option<Facet>> {
+        // ascii files can contain several solids
+        // solid name
+        // facet ...
+        // endsolid name
+        // solid name
+        // ...
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+
+            // keep the line ending, scan_fmt does not like numbers at the very end
+            let lowercase = line.trim_start().to_ascii_lowercase();
+            let trimmed = lowercase.trim_end();
+
+            if trimmed.starts_with("facet") {
+                let triangle = read_ascii_triangle(&lowercase, &mut self.reader)?;
+                return Ok(Some(Facet {
+                    solid: self.current_solid,
+                    ..triangle.into()
+                }));
+            } else if let Some(name) = solid_name(&line) {
+                self.current_solid += 1;
+                if self.solid_names.len() <= self.current_solid as usize {
+                    self.solid_names.push(name);
+                }
+            } else if !trimmed.is_empty() && !trimmed.starts_with("endsolid") {
+                bail!("malformed line '{}'", line.trim());
+            }
+        }
     }
 
     pub fn triangle_count(&mut self) -> Result<u64> {
@@ -89,11 +361,9 @@ impl<T: Read + Seek> Parser<T> {
 
         match self.stl_type {
             StlType::Binary => {
-                // for binary files we can quickly read the first u32
-                // after the header which contains the triangle count
-                self.reader.seek(SeekFrom::Current(-4))?;
-                let count = self.reader.read_u32::<LittleEndian>()? as u64;
-                Ok(count)
+                // for binary files the triangle count is stored
+                // after the header (and checked against the file size)
+                Ok(self.binary_triangle_count)
             }
             StlType::Ascii => {
                 // we have no other choice as parsing the hole file
@@ -108,76 +378,293 @@ impl<T: Read + Seek> Parser<T> {
 
     pub fn read_all(&mut self) -> Result<Mesh> {
         self.rewind()?;
-        let mut triangles = vec![];
 
-        while let Some(triangle) = self.next_triangle() {
-            triangles.push(triangle);
+        if let StlType::Binary = self.stl_type {
+            return self.read_all_binary();
         }
 
-        Ok(Mesh::new(triangles))
+        // read errors are reported like the passes of a lazy mesh do
+        let mut mesh = Mesh::default();
+        while let Some(facet) = self.try_next_triangle()? {
+            mesh.push(facet);
+        }
+
+        Ok(mesh.with_solid_names(self.solid_names.clone()))
+    }
+
+    /// Reads binary triangles in large chunks and decodes them in parallel
+    fn read_all_binary(&mut self) -> Result<Mesh> {
+        let count = self.binary_triangle_count as usize;
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+
+        let mut triangles = vec![Triangle::new([Vec3::zeros(); 3], Vec3::zeros()); count];
+        let mut attributes = vec![0u16; count];
+        let mut data = vec![];
+
+        for (chunk, chunk_attributes) in triangles
+            .chunks_mut(BULK_CHUNK_SIZE)
+            .zip(attributes.chunks_mut(BULK_CHUNK_SIZE))
+        {
+            data.resize(chunk.len() * TRIANGLE_SIZE as usize, 0);
+            self.reader.read_exact(&mut data)?;
+
+            let triangles_per_thread = chunk.len().div_ceil(threads).max(MIN_TRIANGLES_PER_THREAD);
+
+            thread::scope(|scope| {
+                let workers: Vec<_> = chunk
+                    .chunks_mut(triangles_per_thread)
+                    .zip(chunk_attributes.chunks_mut(triangles_per_thread))
+                    .zip(data.chunks(triangles_per_thread * TRIANGLE_SIZE as usize))
+                    .map(|((triangles, attributes), data)| {
+                        scope.spawn(|| decode_triangles(data, triangles, attributes, self.recalculate_normals))
+                    })
+                    .collect();
+
+                workers
+                    .into_iter()
+                    .try_for_each(|worker| worker.join().unwrap_or_else(|_| bail!("decoder thread panicked")))
+            })?;
+        }
+
+        self.binary_index = count as u64;
+
+        if self.mode == ParseMode::Tolerant {
+            for (i, triangle) in triangles.iter().enumerate() {
+                if !is_finite(triangle) {
+                    let offset = self.header_length + i as u64 * TRIANGLE_SIZE;
+                    self.warn(offset, TRIANGLE_SIZE, "invalid vertex coordinates");
+                }
+            }
+
+            let mut finite = triangles.iter().map(is_finite);
+            attributes.retain(|_| finite.next() == Some(true));
+            triangles.retain(is_finite);
+        }
+
+        self.triangles_read = triangles.len() as u64;
+
+        // most files do not use the attributes, the colors are only stored if there are any
+        let color = |attributes: &u16| decode_facet_color(*attributes, &self.color_convention);
+        if !attributes.iter().any(|a| color(a).is_some()) {
+            return Ok(Mesh::new(triangles));
+        }
+
+        let colors = attributes.iter().map(|a| color(a).map(|c| [c; 3])).collect();
+        Ok(Mesh::new(triangles).with_colors(colors))
+    }
+}
+
+/// An stl file, decompressed in memory if it was compressed
+pub enum StlSource {
+    File(fs::File),
+    Decompressed(Cursor<Vec<u8>>),
+}
+
+impl Read for StlSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::File(file) => file.read(buf),
+            Self::Decompressed(cursor) => cursor.read(buf),
+        }
     }
 }
 
-impl Parser<fs::File> {
+impl Seek for StlSource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Self::File(file) => file.seek(pos),
+            Self::Decompressed(cursor) => cursor.seek(pos),
+        }
+    }
+}
+
+impl Parser<StlSource> {
+    /// Opens an stl file, gzip, zip and xz compressed files are decompressed transparently
     pub fn from_file<P: AsRef<Path>>(filename: P, recalculate_normals: bool) -> Result<Self> {
-        let file = fs::File::open(filename)?;
-        (&file).seek(SeekFrom::Start(0))?;
+        Self::from_file_with_mode(filename, recalculate_normals, ParseMode::Strict)
+    }
+
+    pub fn from_file_with_mode<P: AsRef<Path>>(
+        filename: P,
+        recalculate_normals: bool,
+        mode: ParseMode,
+    ) -> Result<Self> {
+        let mut file = fs::File::open(filename)?;
 
-        Self::from_buf(file, recalculate_normals)
+        let source = match decompress(&mut file)? {
+            Some(data) => StlSource::Decompressed(Cursor::new(data)),
+            None => {
+                file.seek(SeekFrom::Start(0))?;
+                StlSource::File(file)
+            }
+        };
+
+        Self::from_buf_with_mode(source, recalculate_normals, mode)
     }
 }
 
-fn deduce_stl_type<T: BufRead + io::Seek>(reader: &mut T) -> Result<StlType> {
+enum Compression {
+    Gzip,
+    Zip,
+    Xz,
+}
+
+fn compression<T: Read + Seek>(reader: &mut T) -> Result<Option<Compression>> {
+    let mut magic = vec![];
+    reader.by_ref().take(6).read_to_end(&mut magic)?;
+    reader.rewind()?;
+
+    Ok(if magic.starts_with(&[0x1F, 0x8B]) {
+        Some(Compression::Gzip)
+    } else if magic.starts_with(b"PK\x03\x04") {
+        Some(Compression::Zip)
+    } else if magic == [0xFD, b'7', b'z', b'X', b'Z', 0x00] {
+        Some(Compression::Xz)
+    } else {
+        None
+    })
+}
+
+/// Whether `decompress` would decompress the data
+pub fn is_compressed<T: Read + Seek>(reader: &mut T) -> Result<bool> {
+    Ok(compression(reader)?.is_some())
+}
+
+/// Decompresses gzip, zip (first stl in the archive) and xz compressed data,
+/// returns `None` if the data is not compressed
+pub fn decompress<T: Read + Seek>(reader: &mut T) -> Result<Option<Vec<u8>>> {
+    let mut data = vec![];
+
+    match compression(reader)? {
+        Some(Compression::Gzip) => {
+            MultiGzDecoder::new(reader).read_to_end(&mut data)?;
+        }
+        Some(Compression::Zip) => {
+            let mut archive = zip::ZipArchive::new(reader)?;
+
+            let Some(name) = archive
+                .file_names()
+                .find(|name| name.to_ascii_lowercase().ends_with(".stl"))
+                .map(str::to_string)
+            else {
+                bail!("cannot find stl in archive")
+            };
+
+            archive.by_name(&name)?.read_to_end(&mut data)?;
+        }
+        Some(Compression::Xz) => {
+            lzma_rs::xz_decompress(&mut BufReader::new(reader), &mut data)?;
+        }
+        None => return Ok(None),
+    }
+
+    Ok(Some(data))
+}
+
+fn deduce_stl_type<T: BufRead + io::Seek>(reader: &mut T, mode: ParseMode) -> Result<StlType> {
+    // tiny files are valid as well, we thus do not rely on read_exact
+    let mut data = vec![];
+    reader.by_ref().take(512).read_to_end(&mut data)?;
+
     // check if file starts with 'solid', though
     // some malformed binary files also start with 'solid'
-    let mut data = vec![0u8; 5];
-    reader.read_exact(&mut data)?;
-
-    let starts_with_solid = data
-        .iter()
-        .zip("solid".chars())
-        .all(|(a, b)| a.to_ascii_lowercase() == b as u8);
+    let starts_with_solid = data.len() >= 5 && data[..5].eq_ignore_ascii_case(b"solid");
 
-    // we thus read a couple more bytes to check if they are ascii characters
-    let mut data = vec![0u8; 128];
-    reader.read_exact(&mut data)?;
-    let following_bytes_are_ascii = data.iter().all(|d| d.is_ascii());
+    // we thus check if the following bytes are ascii characters
+    let following_bytes_are_ascii = data.iter().take(133).all(|d| d.is_ascii());
 
     if starts_with_solid && following_bytes_are_ascii {
         return Ok(StlType::Ascii);
     }
 
-    // skip header
-    reader.rewind()?;
-    reader.seek(SeekFrom::Start(HEADER_SIZE))?;
-
     // check if it is a valid binary file by confirming that the
     // specified triangle count matches the size of the file
     // (the header does not include a magic number)
-    let triangles = reader.read_u32::<LittleEndian>()? as u64;
     let filesize = reader.seek(SeekFrom::End(0))?;
-    if triangles * TRIANGLE_SIZE + HEADER_SIZE + std::mem::size_of::<u32>() as u64 == filesize {
-        return Ok(StlType::Binary);
+    let header_length = HEADER_SIZE + std::mem::size_of::<u32>() as u64;
+
+    if let Some(count) = data.get(HEADER_SIZE as usize..header_length as usize) {
+        let triangles = u32::from_le_bytes(count.try_into()?) as u64;
+        if triangles * TRIANGLE_SIZE + header_length == filesize {
+            return Ok(StlType::Binary);
+        }
+    }
+
+    if mode == ParseMode::Tolerant {
+        // ascii files with some garbage vs binary files with a wrong triangle count
+        let has_facet = data.windows(5).any(|w| w.eq_ignore_ascii_case(b"facet"));
+
+        if starts_with_solid && (has_facet || filesize < header_length) {
+            return Ok(StlType::Ascii);
+        }
+        if filesize >= header_length && is_plausible_binary(&data, filesize) {
+            return Ok(StlType::Binary);
+        }
     }
 
     // not a valid stl file
     bail!("not an STL file")
 }
 
+/// Damaged binary files still have a triangle count close to the file size
+/// or reasonable coordinates, unlike other files renamed to .stl
+fn is_plausible_binary(data: &[u8], filesize: u64) -> bool {
+    let header_length = HEADER_SIZE + std::mem::size_of::<u32>() as u64;
+    let Some(&[a, b, c, d]) = data.get(HEADER_SIZE as usize..header_length as usize) else {
+        return false;
+    };
+
+    let count = u32::from_le_bytes([a, b, c, d]) as u64;
+    let available = filesize.saturating_sub(header_length) / TRIANGLE_SIZE;
+
+    // some trailing data or a few missing triangles
+    if count > 0 && count.abs_diff(available) <= available / 10 + 1 {
+        return true;
+    }
+
+    // at least 3/4 of the first triangles have sensible normals and vertices
+    let triangles: Vec<&[u8]> = data[header_length as usize..]
+        .chunks_exact(TRIANGLE_SIZE as usize)
+        .collect();
+    let plausible = triangles
+        .iter()
+        .filter(|triangle| {
+            triangle[..48].chunks_exact(4).all(|bytes| {
+                let value = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]).abs();
+                value == 0.0 || (1e-6..1e6).contains(&value)
+            })
+        })
+        .count();
+
+    !triangles.is_empty() && plausible * 4 >= triangles.len() * 3
+}
+
 fn read_ascii_line<T: BufRead>(reader: &mut T) -> Result<String> {
     let mut line = String::new();
     reader.read_line(&mut line)?;
     Ok(line.trim_start().to_ascii_lowercase())
 }
 
-fn read_ascii_triangle<T: BufRead>(reader: &mut T) -> Result<Triangle> {
+/// The name of a 'solid name' line (case is preserved)
+fn solid_name(line: &str) -> Option<String> {
+    let line = line.trim();
+    let keyword = line.get(..5)?;
+
+    if keyword.eq_ignore_ascii_case("solid") && (line.len() == 5 || line[5..].starts_with(char::is_whitespace)) {
+        return Some(line[5..].trim().to_string());
+    }
+
+    None
+}
+
+fn read_ascii_triangle<T: BufRead>(facet_line: &str, reader: &mut T) -> Result<Triangle> {
     let mut vertices = [
         Vec3::new(0.0, 0.0, 0.0),
         Vec3::new(0.0, 0.0, 0.0),
         Vec3::new(0.0, 0.0, 0.0),
     ];
 
-    let (nx, ny, nz) = scan_fmt!(&read_ascii_line(reader)?, "facet normal {f} {f} {f}", f32, f32, f32)?;
+    let (nx, ny, nz) = scan_fmt!(facet_line, "facet normal {f} {f} {f}", f32, f32, f32)?;
 
     read_ascii_line(reader)?; // "outer loop"
 
@@ -194,6 +681,42 @@ fn read_ascii_triangle<T: BufRead>(reader: &mut T) -> Result<Triangle> {
     Ok(Triangle::new(vertices, Vec3::new(nx, ny, nz)))
 }
 
+/// Parses the next three floats, Fortran style exponents ('1.0D+00') are accepted as well
+fn parse_vec3<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Option<Vec3> {
+    let mut value = || tokens.next()?.replace(['d', 'D'], "e").parse::<f32>().ok();
+    Some(Vec3::new(value()?, value()?, value()?))
+}
+
+fn is_finite(triangle: &Triangle) -> bool {
+    triangle.vertices.iter().all(|v| v.iter().all(|c| c.is_finite()))
+}
+
+fn decode_triangles(
+    mut data: &[u8],
+    triangles: &mut [Triangle],
+    attributes: &mut [u16],
+    recalculate_normals: bool,
+) -> Result<()> {
+    for (triangle, attributes) in triangles.iter_mut().zip(attributes) {
+        (*triangle, *attributes) = read_triangle(&mut data)?;
+        fix_normal(triangle, recalculate_normals);
+    }
+
+    Ok(())
+}
+
+/// Calculates the normal from the vertices using the right hand rule in case it is missing
+fn fix_normal(triangle: &mut Triangle, recalculate_normals: bool) {
+    if recalculate_normals
+        || triangle.normal == Vec3::new(0.0, 0.0, 0.0)
+        || triangle.normal == Vec3::new(f32::NAN, f32::NAN, f32::NAN)
+    {
+        triangle.normal = (triangle.vertices[1] - triangle.vertices[0])
+            .cross(&(triangle.vertices[2] - triangle.vertices[0]))
+            .normalize();
+    }
+}
+
 fn read_vec3<T: io::Read>(reader: &mut T) -> Result<Vec3> {
     Ok(Vec3::new(
         reader.read_f32::<LittleEndian>()?,
@@ -202,15 +725,48 @@ fn read_vec3<T: io::Read>(reader: &mut T) -> Result<Vec3> {
     ))
 }
 
-fn read_triangle<T: io::Read>(reader: &mut T) -> Result<Triangle> {
+/// The triangle and its attribute bytes
+fn read_triangle<T: io::Read>(reader: &mut T) -> Result<(Triangle, u16)> {
     let n = read_vec3(reader)?;
     let v1 = read_vec3(reader)?;
     let v2 = read_vec3(reader)?;
     let v3 = read_vec3(reader)?;
 
-    reader.read_u16::<LittleEndian>()?; // attributes
+    let attributes = reader.read_u16::<LittleEndian>()?;
+
+    Ok((Triangle::new([v1, v2, v3], n), attributes))
+}
 
-    Ok(Triangle::new([v1, v2, v3], n))
+/// Materialise Magics stores a default color in the header ('COLOR=' followed by RGBA or
+/// 'MATERIAL=' followed by the diffuse, specular and ambient RGBA colors)
+fn deduce_color_convention(header: &[u8]) -> ColorConvention {
+    let find_color = |key: &[u8]| {
+        let pos = header.windows(key.len()).position(|w| w == key)? + key.len();
+        let rgb = header.get(pos..pos + 3)?;
+        Some(Vec3::new(rgb[0] as f32, rgb[1] as f32, rgb[2] as f32) / 255.0)
+    };
+
+    let has_key = |key: &[u8]| header.windows(key.len()).any(|w| w == key);
+
+    if has_key(b"COLOR=") || has_key(b"MATERIAL=") {
+        ColorConvention::Magics {
+            default_color: find_color(b"COLOR=").or_else(|| find_color(b"MATERIAL=")),
+        }
+    } else {
+        ColorConvention::VisCam
+    }
+}
+
+fn decode_facet_color(attributes: u16, color_convention: &ColorConvention) -> Option<Vec3> {
+    let channel = |shift: u16| ((attributes >> shift) & 0x1F) as f32 / 31.0;
+    let is_set = attributes & 0x8000 != 0;
+
+    match color_convention {
+        ColorConvention::VisCam if is_set => Some(Vec3::new(channel(10), channel(5), channel(0))),
+        ColorConvention::VisCam => None,
+        ColorConvention::Magics { .. } if !is_set => Some(Vec3::new(channel(0), channel(5), channel(10))),
+        ColorConvention::Magics { default_color } => *default_color,
+    }
 }
 
 #[cfg(test)]
@@ -238,9 +794,9 @@ mod test {
     fn parser_ascii_broken_test() {
         let reader = Cursor::new(TRI_ASCII_BROKEN);
         let mut parser = Parser::from_buf(reader, false).unwrap();
-        let triangles = parser.read_all().unwrap();
 
-        assert_eq!(triangles.len(), 0);
+        // like the passes of a lazy mesh (mesh_lazy_broken_test)
+        assert!(parser.read_all().is_err());
     }
 
     #[test]
@@ -255,6 +811,207 @@ mod test {
         assert_eq!(mesh[0].vertices[2], Vec3::new(0.0, 1.0, 0.0));
     }
 
+    #[test]
+    fn parser_bin_colors_test() {
+        // red facet
+        let mut viscam = TRI_BIN.to_vec();
+        let len = viscam.len();
+        viscam[len - 2..].copy_from_slice(&(0x8000u16 | 0x1F << 10).to_le_bytes());
+
+        let mut parser = Parser::from_buf(Cursor::new(viscam), false).unwrap();
+        assert_eq!(parser.color_convention(), ColorConvention::VisCam);
+        let mesh = parser.read_all().unwrap();
+        assert_eq!(mesh.facet(0).colors, Some([Vec3::new(1.0, 0.0, 0.0); 3]));
+
+        // blue facet, RGB order
+        let mut magics = TRI_BIN.to_vec();
+        magics[..10].copy_from_slice(b"COLOR=\x00\xFF\x00\xFF");
+        magics[len - 2..].copy_from_slice(&(0x1Fu16 << 10).to_le_bytes());
+
+        let mut parser = Parser::from_buf(Cursor::new(magics.clone()), false).unwrap();
+        let mesh = parser.read_all().unwrap();
+        assert_eq!(mesh.facet(0).colors, Some([Vec3::new(0.0, 0.0, 1.0); 3]));
+
+        // default color
+        magics[len - 2..].copy_from_slice(&0x8000u16.to_le_bytes());
+
+        let mut parser = Parser::from_buf(Cursor::new(magics), false).unwrap();
+        assert_eq!(
+            parser.color_convention(),
+            ColorConvention::Magics {
+                default_color: Some(Vec3::new(0.0, 1.0, 0.0))
+            }
+        );
+        let mesh = parser.read_all().unwrap();
+        assert_eq!(mesh.facet(0).colors, Some([Vec3::new(0.0, 1.0, 0.0); 3]));
+    }
+
+    #[test]
+    fn parser_ascii_multi_solid_test() {
+        let stl = "solid Base\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nvertex 0 1 0\nendloop\nendfacet\n\
+                   endsolid Base\n\
+                   SOLID Lid\nfacet normal 0 0 1\nouter loop\nvertex 0 0 1\nvertex 1 0 1\nvertex 0 1 1\nendloop\nendfacet\n\
+                   endsolid Lid\n";
+        let mut parser = Parser::from_buf(Cursor::new(stl), false).unwrap();
+        let mesh = parser.read_all().unwrap();
+
+        assert_eq!(mesh.len(), 2);
+        assert_eq!(mesh.facet(0).solid, 0);
+        assert_eq!(mesh.facet(1).solid, 1);
+        assert_eq!(mesh[1].vertices[0], Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(mesh.solid_name(&mesh.facet(0)), Some("Base"));
+        assert_eq!(mesh.solid_name(&mesh.facet(1)), Some("Lid"));
+
+        // solids are counted from the start again
+        assert_eq!(parser.triangle_count().unwrap(), 2);
+        assert_eq!(parser.solid_names(), ["Base", "Lid"]);
+    }
+
+    #[test]
+    fn parser_compressed_test() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let mut gz = GzEncoder::new(vec![], Compression::default());
+        gz.write_all(TRI_BIN).unwrap();
+        let gz = gz.finish().unwrap();
+
+        let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+        zip.start_file("triangle.stl", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(TRI_BIN).unwrap();
+        let zip = zip.finish().unwrap().into_inner();
+
+        let mut xz = vec![];
+        lzma_rs::xz_compress(&mut Cursor::new(TRI_BIN), &mut xz).unwrap();
+
+        for compressed in [gz, zip, xz] {
+            let data = decompress(&mut Cursor::new(compressed)).unwrap().unwrap();
+
+            // the binary stl is detected by its decompressed size
+            let mut parser = Parser::from_buf(Cursor::new(data), false).unwrap();
+            let mesh = parser.read_all().unwrap();
+
+            assert_eq!(mesh.len(), 1);
+            assert_eq!(mesh[0].vertices[2], Vec3::new(0.0, 1.0, 0.0));
+        }
+
+        assert_eq!(decompress(&mut Cursor::new(TRI_BIN)).unwrap(), None);
+    }
+
+    #[test]
+    fn parser_tiny_ascii_test() {
+        let stl =
+            "solid\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nvertex 0 1 0\nendloop\nendfacet\n";
+        let mut parser = Parser::from_buf(Cursor::new(stl), false).unwrap();
+
+        assert_eq!(parser.read_all().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn parser_tolerant_binary_test() {
+        // trailing garbage
+        let mut data = TRI_BIN.to_vec();
+        data.extend_from_slice(b"garbage");
+
+        assert!(Parser::from_buf(Cursor::new(data.clone()), false).is_err());
+
+        let mut parser = Parser::from_buf_with_mode(Cursor::new(data), false, ParseMode::Tolerant).unwrap();
+        let mesh = parser.read_all().unwrap();
+
+        assert_eq!(mesh.len(), 1);
+        assert_eq!(mesh[0].vertices[2], Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(
+            parser.warnings(),
+            [ParseWarning {
+                offset: 134,
+                length: 7,
+                message: "trailing data".to_string()
+            }]
+        );
+
+        // truncated in the middle of the second triangle
+        let mut data = TRI_BIN.to_vec();
+        data[80..84].copy_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(&TRI_BIN[84..100]);
+
+        let mut parser = Parser::from_buf_with_mode(Cursor::new(data), false, ParseMode::Tolerant).unwrap();
+
+        assert_eq!(parser.triangle_count().unwrap(), 1);
+        assert_eq!(parser.read_all().unwrap().len(), 1);
+        assert_eq!(parser.triangles_read(), 1);
+        assert_eq!(parser.warnings().len(), 1);
+        assert_eq!(parser.warnings()[0].offset, 134);
+        assert_eq!(parser.warnings()[0].length, 16);
+    }
+
+    #[test]
+    fn parser_tolerant_not_stl_test() {
+        // e.g. a png renamed to .stl
+        let mut data = b"\x89PNG\r\n\x1a\n".to_vec();
+        data.extend((0..1000u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8));
+
+        assert!(Parser::from_buf_with_mode(Cursor::new(data), false, ParseMode::Tolerant).is_err());
+    }
+
+    #[test]
+    fn parser_tolerant_ascii_test() {
+        let stl = "solid messy\n\
+                   \tfacet   normal 0.0E+000  0.0E+000  1.0E+000\n  outer loop\n\
+                   vertex 0 0 0\n  vertex   1.0D+00 0 0\nvertex 0 1 0\nendloop\nendfacet\n\
+                   facet\nouter loop\nvertex 0 0 1\ngarbage\nvertex 1 0 1\nvertex 0 1 1\nendloop\nendfacet\n\
+                   facet normal 0 0 1\nouter loop\nvertex 0 0 2\n";
+
+        assert!(Parser::from_buf(Cursor::new(stl), false).unwrap().read_all().is_err());
+
+        let mut parser = Parser::from_buf_with_mode(Cursor::new(stl), false, ParseMode::Tolerant).unwrap();
+        let mesh = parser.read_all().unwrap();
+
+        assert_eq!(mesh.len(), 2);
+        assert_eq!(mesh[0].normal, Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(mesh[0].vertices[1], Vec3::new(1.0, 0.0, 0.0));
+
+        // the missing normal is recalculated
+        assert_eq!(mesh[1].normal, Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(mesh[1].vertices[0], Vec3::new(0.0, 0.0, 1.0));
+
+        let warnings: Vec<_> = parser.warnings().iter().map(|w| w.message.as_str()).collect();
+        assert_eq!(warnings, ["unknown line", "incomplete facet"]);
+        assert_eq!(parser.warnings()[0].length, 8);
+        assert_eq!(parser.triangles_read(), 2);
+    }
+
+    #[test]
+    fn parser_bulk_binary_test() {
+        // enough triangles for several decoder threads
+        let count = 3 * MIN_TRIANGLES_PER_THREAD as u32 + 7;
+
+        let mut data = vec![0u8; 80];
+        data.extend_from_slice(&count.to_le_bytes());
+        for i in 0..count {
+            let f = i as f32;
+            let values = [0.0, 0.0, 0.0, f, 0.0, 0.0, f + 1.0, 0.0, 0.0, f, 1.0, 0.0];
+            values.iter().for_each(|v| data.extend_from_slice(&v.to_le_bytes()));
+            data.extend_from_slice(&(0x8000u16 | (i % 32) as u16).to_le_bytes());
+        }
+
+        for recalculate_normals in [false, true] {
+            let mut parser = Parser::from_buf(Cursor::new(data.clone()), recalculate_normals).unwrap();
+            let mesh = parser.read_all().unwrap();
+
+            parser.rewind().unwrap();
+            let triangles: Vec<_> = std::iter::from_fn(|| parser.next_triangle()).collect();
+
+            assert_eq!(mesh.len(), count as usize);
+            assert_eq!((&mesh).into_iter().collect::<Vec<_>>(), triangles);
+            assert_eq!(
+                mesh[count as usize - 1].vertices[0],
+                Vec3::new(count as f32 - 1.0, 0.0, 0.0)
+            );
+            assert_eq!(mesh[0].normal, Vec3::new(0.0, 0.0, 1.0));
+        }
+    }
+
     #[test]
     fn mesh_lazy_ascii() {
         let reader = Cursor::new(TRI_ASCII);
@@ -262,9 +1019,10 @@ mod test {
 
         assert_eq!(parser.triangle_count().unwrap(), 2);
 
-        let lazy_mesh = LazyMesh::new(&mut parser);
+        let lazy_mesh = LazyMesh::new(|| Parser::from_buf(Cursor::new(TRI_ASCII), false));
 
-        let triangles = (&lazy_mesh).into_iter().collect::<Vec<Triangle>>();
+        let triangles = (&lazy_mesh).into_iter().map(|f| f.triangle).collect::<Vec<Triangle>>();
+        lazy_mesh.check().unwrap();
         assert_eq!(
             triangles[0],
             Triangle {
@@ -296,9 +1054,10 @@ mod test {
 
         assert_eq!(parser.triangle_count().unwrap(), 1);
 
-        let lazy_mesh = LazyMesh::new(&mut parser);
+        let lazy_mesh = LazyMesh::new(|| Parser::from_buf(Cursor::new(TRI_BIN), false));
 
-        let triangles = (&lazy_mesh).into_iter().collect::<Vec<Triangle>>();
+        let triangles = (&lazy_mesh).into_iter().map(|f| f.triangle).collect::<Vec<Triangle>>();
+        lazy_mesh.check().unwrap();
         assert_eq!(
             triangles[0],
             Triangle {
@@ -311,4 +1070,76 @@ mod test {
             }
         );
     }
+
+    #[test]
+    fn mesh_lazy_concurrent() {
+        let lazy_mesh = LazyMesh::new(|| Parser::from_buf(Cursor::new(TRI_ASCII), false));
+
+        let counts: Vec<usize> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..4)
+                .map(|_| scope.spawn(|| (&lazy_mesh).into_iter().count()))
+                .collect();
+            workers.into_iter().map(|worker| worker.join().unwrap()).collect()
+        });
+
+        assert_eq!(counts, [2; 4]);
+        assert_eq!(lazy_mesh.triangles_read(), 2);
+        assert!(lazy_mesh.warnings().is_empty());
+        lazy_mesh.check().unwrap();
+    }
+
+    #[test]
+    fn mesh_lazy_broken_test() {
+        // the error occurs while reading, after the file was opened
+        let lazy_mesh = LazyMesh::new(|| Parser::from_buf(Cursor::new(TRI_ASCII_BROKEN), false));
+
+        assert_eq!((&lazy_mesh).into_iter().count(), 0);
+        assert!(lazy_mesh.check().is_err());
+
+        let data = &TRI_BIN[..TRI_BIN.len() - 10];
+        let lazy_mesh = LazyMesh::new(|| {
+            let mut parser = Parser::from_buf(Cursor::new(TRI_BIN), false)?;
+            // pretend the file got truncated after opening it
+            parser.reader = BufReader::new(Cursor::new(data));
+            parser.reader.seek(SeekFrom::Start(parser.header_length))?;
+            Ok(parser)
+        });
+
+        assert_eq!((&lazy_mesh).into_iter().count(), 0);
+        assert!(lazy_mesh.check().is_err());
+    }
+
+    #[test]
+    fn mesh_lazy_try_new_test() {
+        let opened = std::sync::atomic::AtomicUsize::new(0);
+        let lazy_mesh = LazyMesh::try_new(|| {
+            opened.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Parser::from_buf(Cursor::new(TRI_ASCII), false)
+        })
+        .unwrap();
+
+        // the first pass continues with the parser opened by try_new
+        assert_eq!((&lazy_mesh).into_iter().count(), 2);
+        assert_eq!((&lazy_mesh).into_iter().count(), 2);
+        assert_eq!(opened.into_inner(), 2);
+
+        assert!(LazyMesh::try_new(|| Parser::from_buf(Cursor::new(b"not an stl"), false)).is_err());
+    }
+
+    #[test]
+    fn is_compressed_test() {
+        assert!(!is_compressed(&mut Cursor::new(TRI_BIN)).unwrap());
+        assert!(is_compressed(&mut Cursor::new(b"\x1F\x8Bcompressed")).unwrap());
+    }
+
+    #[test]
+    fn mesh_lazy_error() {
+        let lazy_mesh = LazyMesh::new(|| Parser::from_buf(Cursor::new(b"not an stl"), false));
+
+        assert_eq!((&lazy_mesh).into_iter().count(), 0);
+        assert!(lazy_mesh.check().is_err());
+
+        // the error is reported once
+        assert!(lazy_mesh.check().is_ok());
+    }
 }
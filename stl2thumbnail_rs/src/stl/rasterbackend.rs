@@ -1,5 +1,8 @@
 use super::aabb::*;
+use super::analysis::MaterialEstimate;
+use super::decimate::decimate;
 use super::mesh::*;
+use super::shadow::ShadowMap;
 use super::zbuffer::*;
 use crate::picture::*;
 
@@ -18,8 +21,31 @@ pub struct RenderOptions {
     pub zoom: f32,
     pub grid_visible: bool,
     pub draw_size_hint: bool,
+    /// renders each solid in a different color
+    pub color_solids: bool,
+    /// renders back faces instead of culling them
+    pub two_sided: bool,
+    /// shown next to the dimensions in the size hint
+    pub material_estimate: Option<MaterialEstimate>,
+    /// opacity (0.0-1.0) of the shadow cast onto the grid plane, none if not set
+    pub shadow_opacity: Option<f32>,
+    /// renders a simplified mesh first if there is a timeout, it takes memory proportional to the mesh
+    pub coarse_proxy: bool,
 }
 
+/// Grid cells along the largest side of the coarse proxy
+const PROXY_RESOLUTION: f32 = 32.0;
+
+// the first solid keeps the default model color
+const SOLID_COLORS: [[f32; 3]; 6] = [
+    [0.0, 0.45, 1.0],
+    [1.0, 0.45, 0.0],
+    [0.2, 0.75, 0.2],
+    [0.85, 0.2, 0.6],
+    [0.95, 0.8, 0.1],
+    [0.5, 0.3, 0.9],
+];
+
 impl Default for RenderOptions {
     fn default() -> Self {
         Self {
@@ -33,10 +59,22 @@ impl Default for RenderOptions {
             grid_visible: true,
             zoom: 1.0,
             draw_size_hint: true,
+            color_solids: false,
+            two_sided: false,
+            material_estimate: None,
+            shadow_opacity: None,
+            coarse_proxy: true,
         }
     }
 }
 
+#[derive(Debug)]
+pub struct Rendering {
+    pub picture: Picture,
+    /// false if the timeout expired and the picture shows the coarse proxy (or is incomplete without one)
+    pub is_final: bool,
+}
+
 #[derive(Debug)]
 pub struct RasterBackend {
     pub render_options: RenderOptions,
@@ -73,7 +111,7 @@ impl RasterBackend {
         proj * view
     }
 
-    pub fn fit_mesh_scale(&self, mesh: impl IntoIterator<Item = Triangle> + Copy) -> (AABB, f32) {
+    pub fn fit_mesh_scale(&self, mesh: impl IntoIterator<Item = Facet> + Copy) -> (AABB, f32) {
         let aabb = AABB::from_iterable(mesh);
         let vp = self.view_projection(1.0);
 
@@ -81,15 +119,69 @@ impl RasterBackend {
         (aabb, scale_for_unitsize(&vp, &aabb))
     }
 
+    /// Renders the mesh, with a timeout a coarse proxy is rendered first and kept if the mesh takes too long
     pub fn render(
         &self,
-        mesh: impl IntoIterator<Item = Triangle> + Copy,
+        mesh: impl IntoIterator<Item = Facet> + Copy,
         model_scale: f32,
         aabb: &AABB,
         timeout: Option<Duration>,
-    ) -> Picture {
-        let start_time = Instant::now();
+    ) -> Rendering {
+        let Some(timeout) = timeout else {
+            return self.draw(mesh, model_scale, aabb, None);
+        };
+
+        // building the proxy is part of the budget
+        let deadline = Instant::now() + timeout;
+        if !self.render_options.coarse_proxy {
+            return self.draw(mesh, model_scale, aabb, Some(deadline));
+        }
+        let proxy = Self::coarse_proxy(mesh, aabb);
+
+        self.render_progressive(mesh, &proxy, model_scale, aabb, deadline)
+    }
 
+    /// A simplified mesh for `render_progressive`, it does not depend on the view
+    pub fn coarse_proxy(mesh: impl IntoIterator<Item = Facet> + Copy, aabb: &AABB) -> Mesh {
+        decimate(mesh, aabb.size().max() / PROXY_RESOLUTION)
+    }
+
+    /// Renders the proxy and then refines it with the mesh until the deadline
+    pub fn render_progressive(
+        &self,
+        mesh: impl IntoIterator<Item = Facet> + Copy,
+        proxy: &Mesh,
+        model_scale: f32,
+        aabb: &AABB,
+        deadline: Instant,
+    ) -> Rendering {
+        // the proxy is always completed, that gives a recognisable silhouette
+        let coarse = self.draw(proxy, model_scale, aabb, None);
+        if Instant::now() > deadline {
+            return Rendering {
+                picture: coarse.picture,
+                is_final: false,
+            };
+        }
+
+        let rendering = self.draw(mesh, model_scale, aabb, Some(deadline));
+        if rendering.is_final {
+            rendering
+        } else {
+            Rendering {
+                picture: coarse.picture,
+                is_final: false,
+            }
+        }
+    }
+
+    fn draw(
+        &self,
+        mesh: impl IntoIterator<Item = Facet> + Copy,
+        model_scale: f32,
+        aabb: &AABB,
+        deadline: Option<Instant>,
+    ) -> Rendering {
         let mut pic = Picture::new(self.width, self.height, &(&self.render_options.background_color).into());
         let mut zbuf = ZBuffer::new(self.width, self.height);
         let mut scaled_aabb = *aabb;
@@ -128,22 +220,50 @@ impl RasterBackend {
             );
         }
 
-        for t in mesh {
-            // timed out?
-            if let Some(timeout) = timeout {
-                let dt = Instant::now() - start_time;
-                if dt > timeout {
-                    // abort
-                    println!("... timeout!");
-                    return pic;
+        // the camera looks from -view_pos, the shadow is only visible from above the ground
+        if let Some(opacity) = self.render_options.shadow_opacity.filter(|_| eye_normal.z < 0.0) {
+            let mut shadow_map = ShadowMap::new(
+                &self.render_options.light_normal,
+                &scaled_aabb,
+                self.width.max(self.height),
+            );
+
+            for Facet { triangle: t, .. } in mesh {
+                // timed out?
+                if deadline.is_some_and(|deadline| Instant::now() > deadline) {
+                    return Rendering {
+                        picture: pic,
+                        is_final: false,
+                    };
                 }
+
+                shadow_map.add_triangle(&t.vertices.map(|v| transform_point(&model, &v)));
+            }
+
+            draw_shadow(&mut pic, &vp, scaled_aabb.lower.z, &shadow_map, opacity);
+        }
+
+        for facet in mesh {
+            let t = &facet.triangle;
+
+            // timed out?
+            if deadline.is_some_and(|deadline| Instant::now() > deadline) {
+                return Rendering {
+                    picture: pic,
+                    is_final: false,
+                };
             }
 
-            let normal = t.normal;
+            let mut normal = t.normal;
 
             // backface culling
             if glm::dot(&eye_normal, &normal) > 0.0 {
-                continue;
+                if !self.render_options.two_sided {
+                    continue;
+                }
+
+                // light the back face
+                normal = -normal;
             }
 
             let v = &t.vertices;
@@ -175,8 +295,11 @@ impl RasterBackend {
                     let p1 = v1.xy();
                     let p2 = v2.xy();
 
-                    let inside =
-                        edge_fn(&p, &p0, &p1) <= 0.0 && edge_fn(&p, &p1, &p2) <= 0.0 && edge_fn(&p, &p2, &p0) <= 0.0;
+                    let edges = [edge_fn(&p, &p0, &p1), edge_fn(&p, &p1, &p2), edge_fn(&p, &p2, &p0)];
+
+                    // the winding does not have to match the normal when rendering two-sided
+                    let inside = edges.iter().all(|&e| e <= 0.0)
+                        || (self.render_options.two_sided && edges.iter().all(|&e| e >= 0.0));
 
                     if inside {
                         // calculate barycentric coordinates
@@ -197,11 +320,20 @@ impl RasterBackend {
                             let diff_color = glm::dot(&normal, &self.render_options.light_normal).max(0.0)
                                 * self.render_options.light_color;
 
+                            // interpolate the vertex colors if there are any
+                            let model_color = match &facet.colors {
+                                Some(c) => w0 * c[0] + w1 * c[1] + w2 * c[2],
+                                None if self.render_options.color_solids => {
+                                    SOLID_COLORS[facet.solid as usize % SOLID_COLORS.len()].into()
+                                }
+                                None => self.render_options.model_color,
+                            };
+
                             // merge
                             let mut color = self.render_options.ambient_color + diff_color;
-                            color.x *= self.render_options.model_color.x;
-                            color.y *= self.render_options.model_color.y;
-                            color.z *= self.render_options.model_color.z;
+                            color.x *= model_color.x;
+                            color.y *= model_color.y;
+                            color.z *= model_color.z;
 
                             pic.set(x, y, &(color.x, color.y, color.z, 1.0).into());
                         }
@@ -211,36 +343,24 @@ impl RasterBackend {
         }
 
         if self.render_options.draw_size_hint {
-            let margin = 3;
-            let text_to_height_ratio = 16;
-
-            let text = format!(
+            let mut text = format!(
                 "{}x{}x{}",
                 aabb.size().x as i32,
                 aabb.size().y as i32,
                 aabb.size().z as i32
             );
 
-            let text_size = pic.height() / text_to_height_ratio;
-
-            pic.fill_rect(
-                0,
-                pic.height() as i32 - (text_size + margin * 2) as i32,
-                pic.width() as i32,
-                pic.height() as i32,
-                &Color::DARK_GRAY,
-            );
+            if let Some(estimate) = &self.render_options.material_estimate {
+                text.push_str(&format!(" {:.1}g", estimate.weight_g));
+            }
 
-            pic.stroke_string(
-                margin,
-                pic.height() - text_size - margin,
-                &text,
-                text_size as f32,
-                &Color::WHITE,
-            );
+            pic.draw_caption(&text);
         }
 
-        pic
+        Rendering {
+            picture: pic,
+            is_final: true,
+        }
     }
 }
 
@@ -274,6 +394,31 @@ fn scale_for_unitsize(mvp: &Mat4, aabb: &AABB) -> f32 {
     1.0 / ((f32::abs(max.x - min.x)).max(f32::abs(max.y - min.y)) / 2.0)
 }
 
+fn draw_shadow(pic: &mut Picture, vp: &Mat4, z: f32, shadow_map: &ShadowMap, opacity: f32) {
+    let inverse_vp = glm::inverse(vp);
+
+    for y in 0..pic.height() {
+        for x in 0..pic.width() {
+            // normalized screen coordinates [-1,1]
+            let nx = 2.0 * ((x as f32 / pic.width() as f32) - 0.5);
+            let ny = 2.0 * ((y as f32 / pic.height() as f32) - 0.5);
+
+            // intersect the view ray with the ground plane
+            let near = transform_point(&inverse_vp, &Vec3::new(nx, ny, -1.0));
+            let far = transform_point(&inverse_vp, &Vec3::new(nx, ny, 1.0));
+            if (far.z - near.z).abs() < f32::EPSILON {
+                continue;
+            }
+            let ground = near + (far - near) * ((z - near.z) / (far.z - near.z));
+
+            let shadow = shadow_map.shadow(&ground);
+            if shadow > 0.0 {
+                pic.alpha_blend(x, y, (0.0, 0.0, 0.0, opacity * shadow).into());
+            }
+        }
+    }
+}
+
 fn draw_grid(pic: &mut Picture, vp: &Mat4, z: f32, color: &Vec3, model_size: Vec3, scale: f32) {
     // draw grid
     let max_xy = model_size.x.max(model_size.y);
@@ -302,3 +447,90 @@ fn draw_grid(pic: &mut Picture, vp: &Mat4, z: f32, color: &Vec3, model_size: Vec
         );
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::stl::parser::Parser;
+
+    fn model_pixels(pic: &Picture) -> usize {
+        let background = pic.get(0, 0);
+        (0..pic.height())
+            .flat_map(|y| (0..pic.width()).map(move |x| (x, y)))
+            .filter(|&(x, y)| pic.get(x, y) != background)
+            .count()
+    }
+
+    #[test]
+    fn render_progressive_test() {
+        let mesh = Parser::from_file("test_models/triangle.stl", true)
+            .unwrap()
+            .read_all()
+            .unwrap();
+
+        let mut backend = RasterBackend::new(64, 64);
+        backend.render_options.grid_visible = false;
+        backend.render_options.draw_size_hint = false;
+        backend.render_options.two_sided = true;
+        let (aabb, scale) = backend.fit_mesh_scale(&mesh);
+
+        let rendering = backend.render(&mesh, scale, &aabb, None);
+        assert!(rendering.is_final);
+        assert!(model_pixels(&rendering.picture) > 0);
+
+        // the proxy is shown if the mesh cannot be rendered in time
+        let proxy = RasterBackend::coarse_proxy(&mesh, &aabb);
+        let rendering = backend.render_progressive(&mesh, &proxy, scale, &aabb, Instant::now());
+        assert!(!rendering.is_final);
+        assert!(model_pixels(&rendering.picture) > 0);
+
+        let rendering = backend.render(&mesh, scale, &aabb, Some(Duration::from_secs(60)));
+        assert!(rendering.is_final);
+
+        // low memory mode renders the mesh directly
+        backend.render_options.coarse_proxy = false;
+        let rendering = backend.render(&mesh, scale, &aabb, Some(Duration::from_secs(60)));
+        assert!(rendering.is_final);
+        assert!(model_pixels(&rendering.picture) > 0);
+    }
+
+    #[test]
+    fn render_shadow_test() {
+        // a small square floating above a tiny triangle on the ground
+        let up = Vec3::new(0.0, 0.0, 1.0);
+        let v = |x: f32, y: f32, z: f32| Vec3::new(x, y, z);
+        let mesh = Mesh::new(vec![
+            Triangle::new([v(-0.5, -0.5, 4.0), v(0.5, -0.5, 4.0), v(0.5, 0.5, 4.0)], up),
+            Triangle::new([v(-0.5, -0.5, 4.0), v(0.5, 0.5, 4.0), v(-0.5, 0.5, 4.0)], up),
+            Triangle::new([v(0.0, 0.0, 0.0), v(0.1, 0.0, 0.0), v(0.0, 0.1, 0.0)], up),
+        ]);
+
+        let mut backend = RasterBackend::new(128, 128);
+        backend.render_options.grid_visible = false;
+        backend.render_options.draw_size_hint = false;
+        backend.render_options.two_sided = true;
+        let (aabb, scale) = backend.fit_mesh_scale(&mesh);
+
+        let plain = backend.render(&mesh, scale, &aabb, None).picture;
+        backend.render_options.shadow_opacity = Some(0.5);
+        let shadowed = backend.render(&mesh, scale, &aabb, None).picture;
+
+        // the point on the ground below the square along the light
+        let light = backend.render_options.light_normal;
+        let ground = Vec3::new(0.0, 0.0, 4.0) - light * (4.0 / light.z);
+
+        let model = Mat4::identity()
+            .append_translation(&-aabb.center())
+            .append_scaling(scale);
+        let screen = transform_point(&(backend.view_projection(backend.render_options.zoom) * model), &ground);
+        let x = ((screen.x + 1.0) / 2.0 * 128.0) as u32;
+        let y = ((screen.y + 1.0) / 2.0 * 128.0) as u32;
+
+        let background = plain.get(0, 0);
+        assert_eq!(plain.get(x, y), background);
+
+        let pixel = shadowed.get(x, y);
+        assert!(pixel.r < background.r && pixel.g < background.g && pixel.b < background.b);
+        assert_eq!(shadowed.get(0, 0), background);
+    }
+}
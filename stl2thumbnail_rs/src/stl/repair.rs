@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+
+use super::analysis::vertex_key;
+use super::mesh::{Triangle, Vec3};
+
+/// Orients the triangles of each shell consistently and recalculates the normals from the winding
+///
+/// Closed shells are turned outside out by their signed volume (cavities are thus flipped as well,
+/// which does not matter for rendering), open shells follow the majority of the stored normals.
+/// The per vertex colors (empty or one entry per triangle) are flipped along.
+pub fn repair_orientation(triangles: &mut [Triangle], colors: &mut [Option<[Vec3; 3]>]) {
+    // vertices are welded if their coordinates match exactly
+    let mut vertex_ids: HashMap<[u32; 3], usize> = HashMap::new();
+    let ids: Vec<[usize; 3]> = triangles
+        .iter()
+        .map(|t| {
+            t.vertices.map(|v| {
+                let next_id = vertex_ids.len();
+                *vertex_ids.entry(vertex_key(&v)).or_insert(next_id)
+            })
+        })
+        .collect();
+
+    // undirected edge -> triangles and whether they traverse it from the lower to the higher id
+    let mut edges: HashMap<(usize, usize), Vec<(usize, bool)>> = HashMap::new();
+    for (i, [a, b, c]) in ids.iter().copied().enumerate() {
+        for (a, b) in [(a, b), (b, c), (c, a)] {
+            if a != b {
+                edges.entry((a.min(b), a.max(b))).or_default().push((i, a < b));
+            }
+        }
+    }
+
+    let mut flipped = vec![false; triangles.len()];
+    let mut visited = vec![false; triangles.len()];
+
+    for start in 0..triangles.len() {
+        if visited[start] {
+            continue;
+        }
+
+        // propagate the orientation of the first triangle to its shell,
+        // neighbors across a manifold edge have to traverse it in opposite directions
+        let mut shell = vec![start];
+        let mut is_closed = true;
+        visited[start] = true;
+
+        let mut i = 0;
+        while i < shell.len() {
+            let t = shell[i];
+            let [a, b, c] = ids[t];
+
+            for (a, b) in [(a, b), (b, c), (c, a)] {
+                let Some(adjacent) = edges.get(&(a.min(b), a.max(b))) else {
+                    continue;
+                };
+
+                match adjacent[..] {
+                    [(t0, d0), (t1, d1)] => {
+                        let (n, same_direction) = if t0 == t { (t1, d0 == d1) } else { (t0, d0 == d1) };
+                        if !visited[n] {
+                            visited[n] = true;
+                            flipped[n] = flipped[t] ^ same_direction;
+                            shell.push(n);
+                        }
+                    }
+                    [_] => is_closed = false,
+                    _ => (), // non-manifold
+                }
+            }
+            i += 1;
+        }
+
+        let wound = |t: usize| {
+            let [a, b, c] = triangles[t].vertices;
+            if flipped[t] {
+                [a, c, b]
+            } else {
+                [a, b, c]
+            }
+        };
+
+        let turn_over = if is_closed {
+            let volume: f64 = shell
+                .iter()
+                .map(|&t| {
+                    let [a, b, c] = wound(t).map(|v| v.map(|c| c as f64));
+                    a.dot(&b.cross(&c))
+                })
+                .sum();
+            volume < 0.0
+        } else {
+            let agreement: f32 = shell
+                .iter()
+                .map(|&t| {
+                    let [a, b, c] = wound(t);
+                    (b - a).cross(&(c - a)).dot(&triangles[t].normal)
+                })
+                .sum();
+            agreement < 0.0
+        };
+
+        if turn_over {
+            for &t in &shell {
+                flipped[t] = !flipped[t];
+            }
+        }
+    }
+
+    for (i, (triangle, flipped)) in triangles.iter_mut().zip(flipped).enumerate() {
+        if flipped {
+            triangle.vertices.swap(1, 2);
+            if let Some(Some(colors)) = colors.get_mut(i) {
+                colors.swap(1, 2);
+            }
+        }
+
+        let [a, b, c] = triangle.vertices;
+        let normal = (b - a).cross(&(c - a));
+        if normal != Vec3::zeros() {
+            triangle.normal = normal.normalize();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::stl::mesh::test::cuboid;
+
+    fn assert_outwards(triangles: &[Triangle], center: Vec3) {
+        for t in triangles {
+            let centroid = (t.vertices[0] + t.vertices[1] + t.vertices[2]) / 3.0;
+            assert!(t.normal.dot(&(centroid - center)) > 0.0, "{t:?}");
+
+            // the normal matches the winding
+            let winding = (t.vertices[1] - t.vertices[0]).cross(&(t.vertices[2] - t.vertices[0]));
+            assert!(winding.dot(&t.normal) > 0.0);
+        }
+    }
+
+    #[test]
+    fn repair_flipped_faces_test() {
+        let mut triangles = cuboid(Vec3::zeros(), Vec3::repeat(1.0));
+        triangles.extend(cuboid(Vec3::new(3.0, 0.0, 0.0), Vec3::repeat(1.0)));
+
+        // flip some faces and store bogus normals
+        for t in triangles.iter_mut().step_by(3) {
+            t.vertices.swap(0, 1);
+            t.normal = Vec3::new(0.0, 0.0, 1.0);
+        }
+
+        repair_orientation(&mut triangles, &mut []);
+
+        assert_outwards(&triangles[..12], Vec3::new(0.5, 0.5, 0.5));
+        assert_outwards(&triangles[12..], Vec3::new(3.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn repair_inside_out_test() {
+        let mut triangles = cuboid(Vec3::zeros(), Vec3::repeat(1.0));
+        triangles.iter_mut().for_each(|t| t.vertices.swap(1, 2));
+
+        repair_orientation(&mut triangles, &mut []);
+
+        assert_outwards(&triangles, Vec3::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn repair_open_shell_test() {
+        // a quad facing up with inconsistent winding
+        let up = Vec3::new(0.0, 0.0, 1.0);
+        let mut triangles = vec![
+            Triangle::new(
+                [
+                    Vec3::new(0.0, 0.0, 0.0),
+                    Vec3::new(1.0, 0.0, 0.0),
+                    Vec3::new(1.0, 1.0, 0.0),
+                ],
+                up,
+            ),
+            Triangle::new(
+                [
+                    Vec3::new(0.0, 0.0, 0.0),
+                    Vec3::new(0.0, 1.0, 0.0),
+                    Vec3::new(1.0, 1.0, 0.0),
+                ],
+                up,
+            ),
+        ];
+
+        repair_orientation(&mut triangles, &mut []);
+
+        assert_eq!(triangles[0].normal, up);
+        assert_eq!(triangles[1].normal, up);
+    }
+}
@@ -0,0 +1,167 @@
+use super::aabb::AABB;
+use super::mesh::*;
+use super::zbuffer::ZBuffer;
+
+/// Width of the soft shadow edge relative to the size of the map
+const SOFTNESS: f32 = 0.02;
+
+/// Samples per axis for the soft edges (percentage closer filtering)
+const SAMPLES: i32 = 5;
+
+/// Depth map of a mesh seen from a directional light, the depth grows towards the light
+pub struct ShadowMap {
+    depth: ZBuffer,
+    size: u32,
+    // light space
+    light: Vec3,
+    u: Vec3,
+    v: Vec3,
+    lower: Vec2,
+    texel_size: f32,
+}
+
+impl ShadowMap {
+    /// An empty map of `size` x `size` texels covering everything within `aabb`
+    pub fn new(light_normal: &Vec3, aabb: &AABB, size: u32) -> Self {
+        let light = light_normal.normalize();
+        let helper = if light.z.abs() < 0.9 {
+            Vec3::new(0.0, 0.0, 1.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let u = helper.cross(&light).normalize();
+        let v = light.cross(&u);
+
+        let mut lower = Vec2::new(f32::MAX, f32::MAX);
+        let mut upper = Vec2::new(f32::MIN, f32::MIN);
+
+        for i in 0..8 {
+            let corner = Vec3::new(
+                if i & 1 == 0 { aabb.lower.x } else { aabb.upper.x },
+                if i & 2 == 0 { aabb.lower.y } else { aabb.upper.y },
+                if i & 4 == 0 { aabb.lower.z } else { aabb.upper.z },
+            );
+            let p = Vec2::new(corner.dot(&u), corner.dot(&v));
+
+            lower = glm::min2(&lower, &p);
+            upper = glm::max2(&upper, &p);
+        }
+
+        // room for the soft edges
+        let extent = (upper - lower).max() / (1.0 - 2.0 * SOFTNESS);
+        let lower = (lower + upper) / 2.0 - Vec2::new(extent, extent) / 2.0;
+
+        Self {
+            depth: ZBuffer::new(size, size),
+            size,
+            light,
+            u,
+            v,
+            lower,
+            texel_size: extent.max(f32::EPSILON) / size as f32,
+        }
+    }
+
+    /// Renders a triangle into the map, both sides cast shadows
+    pub fn add_triangle(&mut self, vertices: &[Vec3; 3]) {
+        let [v0, v1, v2] = vertices.map(|v| self.to_light_space(&v));
+
+        let min_x = v0.x.min(v1.x).min(v2.x).max(0.0) as u32;
+        let min_y = v0.y.min(v1.y).min(v2.y).max(0.0) as u32;
+        let max_x = (v0.x.max(v1.x).max(v2.x).max(0.0) as u32).min(self.size - 1);
+        let max_y = (v0.y.max(v1.y).max(v2.y).max(0.0) as u32).min(self.size - 1);
+
+        let area = edge_fn(&v0.xy(), &v1.xy(), &v2.xy());
+        if area == 0.0 {
+            return;
+        }
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                // texel center
+                let p = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+
+                let w0 = edge_fn(&v1.xy(), &v2.xy(), &p) / area;
+                let w1 = edge_fn(&v2.xy(), &v0.xy(), &p) / area;
+                let w2 = edge_fn(&v0.xy(), &v1.xy(), &p) / area;
+
+                if w0 >= 0.0 && w1 >= 0.0 && w2 >= 0.0 {
+                    self.depth.test_and_set(x, y, w0 * v0.z + w1 * v1.z + w2 * v2.z);
+                }
+            }
+        }
+    }
+
+    /// Fraction (0.0-1.0) of the light blocked at `p`
+    pub fn shadow(&self, p: &Vec3) -> f32 {
+        let p = self.to_light_space(p);
+
+        let radius = SOFTNESS * self.size as f32;
+        let step = 2.0 * radius / (SAMPLES - 1) as f32;
+        let bias = self.texel_size;
+
+        let mut blocked = 0;
+        for i in 0..SAMPLES {
+            for j in 0..SAMPLES {
+                let x = p.x - radius + i as f32 * step;
+                let y = p.y - radius + j as f32 * step;
+
+                if x < 0.0 || y < 0.0 {
+                    continue;
+                }
+
+                if self
+                    .depth
+                    .get(x as u32, y as u32)
+                    .is_some_and(|depth| depth > p.z + bias)
+                {
+                    blocked += 1;
+                }
+            }
+        }
+
+        blocked as f32 / (SAMPLES * SAMPLES) as f32
+    }
+
+    /// Texel coordinates and depth
+    fn to_light_space(&self, p: &Vec3) -> Vec3 {
+        Vec3::new(
+            (p.dot(&self.u) - self.lower.x) / self.texel_size,
+            (p.dot(&self.v) - self.lower.y) / self.texel_size,
+            p.dot(&self.light),
+        )
+    }
+}
+
+fn edge_fn(a: &Vec2, b: &Vec2, c: &Vec2) -> f32 {
+    (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shadow_map_test() {
+        // a horizontal square floating above the ground, lit from straight above
+        let aabb = AABB {
+            lower: Vec3::new(-2.0, -2.0, 0.0),
+            upper: Vec3::new(2.0, 2.0, 1.0),
+        };
+        let mut map = ShadowMap::new(&Vec3::new(0.0, 0.0, 1.0), &aabb, 64);
+
+        let [a, b, c, d] = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].map(|(x, y)| Vec3::new(x, y, 1.0));
+        map.add_triangle(&[a, b, c]);
+        map.add_triangle(&[a, d, c]); // the winding does not matter
+
+        assert_eq!(map.shadow(&Vec3::new(0.0, 0.0, 0.0)), 1.0);
+        assert_eq!(map.shadow(&Vec3::new(1.9, 0.0, 0.0)), 0.0);
+
+        // soft edge
+        let edge = map.shadow(&Vec3::new(1.0, 0.0, 0.0));
+        assert!(edge > 0.2 && edge < 0.8, "{edge}");
+
+        // nothing is above the square
+        assert_eq!(map.shadow(&Vec3::new(0.0, 0.0, 1.0)), 0.0);
+    }
+}
@@ -24,4 +24,12 @@ impl ZBuffer {
 
         false
     }
+
+    pub fn get(&self, x: u32, y: u32) -> Option<f32> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        Some(self.data[(y * self.width + x) as usize])
+    }
 }
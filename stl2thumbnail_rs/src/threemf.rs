@@ -4,7 +4,39 @@ use std::{
     path::Path,
 };
 
-use crate::picture::Picture;
+use crate::{
+    gcode::{self, Metadata, PreviewInfo},
+    picture::{Color, Picture},
+};
+
+pub(crate) const THUMBNAIL_RELATIONSHIP: &str =
+    "http://schemas.openxmlformats.org/package/2006/relationships/metadata/thumbnail";
+const MODEL_RELATIONSHIP: &str = "http://schemas.microsoft.com/3dmanufacturing/2013/01/3dmodel";
+
+/// A build plate of a Bambu or Orca project
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plate {
+    /// plate number (starting at 1)
+    pub index: u32,
+    /// shaded preview ('Metadata/plate_<index>.png')
+    pub thumbnail: Option<String>,
+    /// low resolution preview ('Metadata/plate_<index>_small.png')
+    pub small_thumbnail: Option<String>,
+    /// top view ('Metadata/top_<index>.png')
+    pub top_thumbnail: Option<String>,
+}
+
+impl Plate {
+    /// The best available preview of the plate
+    ///
+    /// 'pick_<index>.png' is ignored as it is an object id mask rather than a preview.
+    pub fn preview(&self) -> Option<&str> {
+        self.thumbnail
+            .as_deref()
+            .or(self.small_thumbnail.as_deref())
+            .or(self.top_thumbnail.as_deref())
+    }
+}
 
 pub fn extract_preview_from_file<P: AsRef<Path>>(filename: P) -> Result<Picture> {
     let file = std::fs::File::open(filename)?;
@@ -17,6 +49,14 @@ where
 {
     let mut archive = zip::ZipArchive::new(r)?;
 
+    // the package thumbnail is referenced by the package relationships ('_rels/.rels')
+    // and can be any png or jpeg within the archive
+    for target in relationship_targets(&mut archive, "", THUMBNAIL_RELATIONSHIP) {
+        if let Ok(picture) = read_picture(&mut archive, &target) {
+            return Ok(picture);
+        }
+    }
+
     // depending on the slicer, the thumbnail gets stored in different locations
     //
     // Prusa, Cura: 'Metadata/thumbnail.png'
@@ -25,25 +65,494 @@ where
     // Prusa slicer generates a single thumbnail for all build plates
     // Bambu slicer generates one thumbnail per build plate (we show the thumbnail of the first build plate)
 
-    for filename in ["Metadata/thumbnail.png", "Metadata/plate_1.png"] {
-        if let Ok(mut thumbnail) = archive.by_name(filename) {
-            let mut buffer = vec![];
-            thumbnail.read_to_end(&mut buffer)?;
-            let image = image::load_from_memory(&buffer)?;
+    if let Ok(picture) = read_picture(&mut archive, "Metadata/thumbnail.png") {
+        return Ok(picture);
+    }
 
-            return Ok(Picture::from_img_buffer(image.to_rgba8()));
+    for plate in plates(&mut archive).unwrap_or_default() {
+        if let Some(picture) = plate
+            .preview()
+            .and_then(|preview| read_picture(&mut archive, preview).ok())
+        {
+            return Ok(picture);
+        }
+    }
+
+    // use the first object thumbnail referenced by the model part relationships
+    // (e.g., '3D/_rels/3dmodel.model.rels')
+    let mut models = relationship_targets(&mut archive, "", MODEL_RELATIONSHIP);
+    if models.is_empty() {
+        models.push("3D/3dmodel.model".to_string());
+    }
+
+    for model in models {
+        for target in relationship_targets(&mut archive, &model, THUMBNAIL_RELATIONSHIP) {
+            if let Ok(picture) = read_picture(&mut archive, &target) {
+                return Ok(picture);
+            }
         }
     }
 
     bail!("Cannot find thumbnail in 3mf")
 }
 
+/// Returns the targets of all relationships of the given type whose source is `part`
+///
+/// Use an empty `part` for the package relationships. The targets are resolved to paths within the archive.
+pub(crate) fn relationship_targets<R>(archive: &mut zip::ZipArchive<R>, part: &str, kind: &str) -> Vec<String>
+where
+    R: Read + Seek,
+{
+    // the relationships of '<dir>/<name>' are stored in '<dir>/_rels/<name>.rels'
+    let part = part.trim_start_matches('/');
+    let (dir, name) = part.rsplit_once('/').unwrap_or(("", part));
+    let rels = if dir.is_empty() {
+        format!("_rels/{name}.rels")
+    } else {
+        format!("{dir}/_rels/{name}.rels")
+    };
+
+    let Ok(content) = read_string(archive, &rels) else {
+        return vec![];
+    };
+    let Ok(doc) = roxmltree::Document::parse(&content) else {
+        return vec![];
+    };
+
+    doc.descendants()
+        .filter(|n| n.has_tag_name("Relationship") && n.attribute("Type") == Some(kind))
+        .filter(|n| n.attribute("TargetMode") != Some("External"))
+        .filter_map(|n| n.attribute("Target"))
+        .map(|target| resolve_part_name(dir, target))
+        .collect()
+}
+
+/// Resolves a relationship target relative to the directory of its source part
+fn resolve_part_name(dir: &str, target: &str) -> String {
+    let mut segments: Vec<&str> = if target.starts_with('/') {
+        vec![]
+    } else {
+        dir.split('/').filter(|s| !s.is_empty()).collect()
+    };
+
+    for segment in target.split('/') {
+        match segment {
+            "" | "." => (),
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+
+    segments.join("/")
+}
+
+/// Lists the build plates of a Bambu or Orca project
+///
+/// The plates are read from 'Metadata/model_settings.config'. Projects without that file
+/// are searched for 'Metadata/plate_<index>.png' instead.
+pub fn list_plates<R>(r: R) -> Result<Vec<Plate>>
+where
+    R: Read + Seek,
+{
+    let mut archive = zip::ZipArchive::new(r)?;
+    plates(&mut archive)
+}
+
+pub fn extract_plate_preview_from_file<P: AsRef<Path>>(filename: P, plate: u32) -> Result<Picture> {
+    let file = std::fs::File::open(filename)?;
+    extract_plate_preview(file, plate)
+}
+
+/// Extracts the preview of a single build plate (starting at 1)
+pub fn extract_plate_preview<R>(r: R, plate: u32) -> Result<Picture>
+where
+    R: Read + Seek,
+{
+    let mut archive = zip::ZipArchive::new(r)?;
+
+    let Some(preview) = plates(&mut archive)?
+        .into_iter()
+        .find(|p| p.index == plate)
+        .and_then(|p| p.preview().map(str::to_string))
+    else {
+        bail!("Cannot find plate {plate} in 3mf")
+    };
+
+    read_picture(&mut archive, &preview)
+}
+
+pub fn extract_plates_grid_from_file<P: AsRef<Path>>(filename: P) -> Result<Picture> {
+    let file = std::fs::File::open(filename)?;
+    extract_plates_grid(file)
+}
+
+/// Composes the previews of all build plates into a single picture
+///
+/// The plates are arranged row by row on a (nearly) square grid.
+pub fn extract_plates_grid<R>(r: R) -> Result<Picture>
+where
+    R: Read + Seek,
+{
+    let mut archive = zip::ZipArchive::new(r)?;
+
+    // plates without a (readable) thumbnail are left out
+    let previews: Vec<Picture> = plates(&mut archive)?
+        .iter()
+        .filter_map(|plate| read_picture(&mut archive, plate.preview()?).ok())
+        .collect();
+
+    if previews.is_empty() {
+        bail!("Cannot find plates in 3mf")
+    }
+
+    let cols = (previews.len() as f32).sqrt().ceil() as u32;
+    let rows = (previews.len() as u32).div_ceil(cols);
+    let cell_width = previews.iter().map(|p| p.width()).max().unwrap_or(0);
+    let cell_height = previews.iter().map(|p| p.height()).max().unwrap_or(0);
+
+    let mut grid = Picture::new(cols * cell_width, rows * cell_height, &Color::TRANSPARENT);
+
+    for (i, preview) in previews.iter().enumerate() {
+        let (col, row) = (i as u32 % cols, i as u32 / cols);
+
+        // center the preview within its cell
+        let x = col * cell_width + (cell_width - preview.width()) / 2;
+        let y = row * cell_height + (cell_height - preview.height()) / 2;
+
+        grid.overlay(preview, x as i64, y as i64);
+    }
+
+    Ok(grid)
+}
+
+/// Returns true for sliced Bambu or Orca print packages ('.gcode.3mf')
+///
+/// Print packages contain the gcode of the sliced plates ('Metadata/plate_<index>.gcode').
+pub fn is_print_package<R>(r: R) -> bool
+where
+    R: Read + Seek,
+{
+    zip::ZipArchive::new(r).is_ok_and(|archive| sliced_plate(&archive).is_some())
+}
+
+pub fn is_print_package_from_file<P: AsRef<Path>>(filename: P) -> bool {
+    std::fs::File::open(filename).is_ok_and(is_print_package)
+}
+
+pub fn extract_print_previews_from_file<P: AsRef<Path>>(filename: P) -> Result<Vec<Picture>> {
+    let file = std::fs::File::open(filename)?;
+    extract_print_previews(file)
+}
+
+/// Extracts the previews of the sliced plate of a print package
+///
+/// These are the plate thumbnails and the previews embedded into the plate's gcode.
+pub fn extract_print_previews<R>(r: R) -> Result<Vec<Picture>>
+where
+    R: Read + Seek,
+{
+    let mut archive = zip::ZipArchive::new(r)?;
+    let Some(plate) = sliced_plate(&archive) else {
+        bail!("Not a print package")
+    };
+
+    let mut previews = vec![];
+    for filename in plate_thumbnails(plate) {
+        if let Ok(picture) = read_picture(&mut archive, &filename) {
+            previews.push(picture);
+        }
+    }
+
+    let gcode = read_bytes(&mut archive, &format!("Metadata/plate_{plate}.gcode"))?;
+    previews.extend(gcode::extract_previews_from_data(&gcode).unwrap_or_default());
+
+    Ok(previews)
+}
+
+pub fn list_print_previews_from_file<P: AsRef<Path>>(filename: P) -> Result<Vec<PreviewInfo>> {
+    let file = std::fs::File::open(filename)?;
+    list_print_previews(file)
+}
+
+/// Lists the previews of the sliced plate of a print package without decoding them
+pub fn list_print_previews<R>(r: R) -> Result<Vec<PreviewInfo>>
+where
+    R: Read + Seek,
+{
+    let mut archive = zip::ZipArchive::new(r)?;
+    let Some(plate) = sliced_plate(&archive) else {
+        bail!("Not a print package")
+    };
+
+    let mut previews = vec![];
+    for filename in plate_thumbnails(plate) {
+        if let Ok(data) = read_bytes(&mut archive, &filename) {
+            let reader = image::ImageReader::new(std::io::Cursor::new(data)).with_guessed_format()?;
+            let format = reader.format().map(|f| f.extensions_str()[0]).unwrap_or("unknown");
+            let format = format.to_string();
+            let (width, height) = reader.into_dimensions()?;
+
+            previews.push(PreviewInfo { width, height, format });
+        }
+    }
+
+    let gcode = read_bytes(&mut archive, &format!("Metadata/plate_{plate}.gcode"))?;
+    previews.extend(gcode::list_previews_from_data(&gcode).unwrap_or_default());
+
+    Ok(previews)
+}
+
+pub fn extract_print_metadata_from_file<P: AsRef<Path>>(filename: P) -> Result<Metadata> {
+    let file = std::fs::File::open(filename)?;
+    extract_print_metadata(file)
+}
+
+/// Reads the print information of a 3mf
+///
+/// The values reported in 'Metadata/slice_info.config' take precedence over the ones in the plate's gcode.
+/// Project files that were not sliced yield empty metadata.
+pub fn extract_print_metadata<R>(r: R) -> Result<Metadata>
+where
+    R: Read + Seek,
+{
+    let mut archive = zip::ZipArchive::new(r)?;
+
+    let plate = sliced_plate(&archive);
+
+    let mut metadata = match plate {
+        Some(plate) => {
+            gcode::extract_metadata_from_data(&read_bytes(&mut archive, &format!("Metadata/plate_{plate}.gcode"))?)?
+        }
+        None => Metadata::default(),
+    };
+
+    if let Ok(slice_info) = read_string(&mut archive, "Metadata/slice_info.config") {
+        apply_slice_info(&slice_info, plate.unwrap_or(1), &mut metadata)?;
+    }
+
+    Ok(metadata)
+}
+
+fn apply_slice_info(slice_info: &str, plate: u32, metadata: &mut Metadata) -> Result<()> {
+    // <config>
+    //   <plate>
+    //     <metadata key="index" value="1"/>
+    //     <metadata key="prediction" value="2301"/>
+    //     <metadata key="weight" value="6.54"/>
+    //     <filament id="1" type="PLA" color="#00AE42" used_m="2.19" used_g="6.54" />
+    //     ...
+    //   </plate>
+    // </config>
+    let doc = roxmltree::Document::parse(slice_info)?;
+
+    for plate_node in doc.descendants().filter(|n| n.has_tag_name("plate")) {
+        let metadata_value = |key: &str| {
+            plate_node
+                .children()
+                .find(|n| n.has_tag_name("metadata") && n.attribute("key") == Some(key))
+                .and_then(|n| n.attribute("value"))
+        };
+
+        if metadata_value("index").and_then(|v| v.parse().ok()) != Some(plate) {
+            continue;
+        }
+
+        if let Some(prediction) = metadata_value("prediction").and_then(|v| v.parse::<f32>().ok()) {
+            metadata.estimated_printing_time = Some(prediction as u64);
+        }
+
+        if let Some(weight) = metadata_value("weight").and_then(|v| v.parse().ok()) {
+            metadata.filament_used_g = Some(weight);
+        }
+
+        if let Some(nozzle_diameter) = metadata_value("nozzle_diameters")
+            .and_then(|v| v.split(',').next())
+            .and_then(|v| v.trim().parse().ok())
+        {
+            metadata.nozzle_diameter = Some(nozzle_diameter);
+        }
+
+        let filaments: Vec<_> = plate_node.children().filter(|n| n.has_tag_name("filament")).collect();
+
+        if !filaments.is_empty() {
+            let types: Vec<_> = filaments.iter().filter_map(|n| n.attribute("type")).collect();
+            let used_m: Vec<f32> = filaments
+                .iter()
+                .filter_map(|n| n.attribute("used_m")?.parse().ok())
+                .collect();
+
+            metadata.filament_type = Some(types.join(";")).filter(|t| !t.is_empty());
+            if !used_m.is_empty() {
+                metadata.filament_used_mm = Some(used_m.iter().sum::<f32>() * 1000.0);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the lowest plate index with gcode
+fn sliced_plate<R>(archive: &zip::ZipArchive<R>) -> Option<u32>
+where
+    R: Read + Seek,
+{
+    archive
+        .file_names()
+        .filter_map(|name| {
+            name.strip_prefix("Metadata/plate_")?
+                .strip_suffix(".gcode")?
+                .parse()
+                .ok()
+        })
+        .min()
+}
+
+fn plate_thumbnails(plate: u32) -> [String; 2] {
+    [
+        format!("Metadata/plate_{plate}.png"),
+        format!("Metadata/plate_{plate}_small.png"),
+    ]
+}
+
+fn plates<R>(archive: &mut zip::ZipArchive<R>) -> Result<Vec<Plate>>
+where
+    R: Read + Seek,
+{
+    let mut plates = match read_string(archive, "Metadata/model_settings.config") {
+        Ok(config) => plates_from_model_settings(&config)?,
+        Err(_) => vec![],
+    };
+
+    // fall back to the file names
+    if plates.is_empty() {
+        let mut indices: Vec<u32> = archive
+            .file_names()
+            .filter_map(|name| name.strip_prefix("Metadata/plate_")?.strip_suffix(".png")?.parse().ok())
+            .collect();
+        indices.sort_unstable();
+
+        plates = indices
+            .into_iter()
+            .map(|index| Plate {
+                index,
+                thumbnail: Some(format!("Metadata/plate_{index}.png")),
+                small_thumbnail: None,
+                top_thumbnail: None,
+            })
+            .collect();
+    }
+
+    // the config does not reference the small thumbnails
+    for plate in &mut plates {
+        if plate.small_thumbnail.is_none() {
+            let small_thumbnail = format!("Metadata/plate_{}_small.png", plate.index);
+            if archive.index_for_name(&small_thumbnail).is_some() {
+                plate.small_thumbnail = Some(small_thumbnail);
+            }
+        }
+    }
+
+    Ok(plates)
+}
+
+fn plates_from_model_settings(config: &str) -> Result<Vec<Plate>> {
+    // <config>
+    //   <plate>
+    //     <metadata key="plater_id" value="1"/>
+    //     <metadata key="thumbnail_file" value="Metadata/plate_1.png"/>
+    //     <metadata key="top_file" value="Metadata/top_1.png"/>
+    //     ...
+    //   </plate>
+    // </config>
+    let doc = roxmltree::Document::parse(config)?;
+
+    let mut plates = vec![];
+
+    for plate_node in doc.descendants().filter(|n| n.has_tag_name("plate")) {
+        let metadata = |key: &str| {
+            plate_node
+                .children()
+                .find(|n| n.has_tag_name("metadata") && n.attribute("key") == Some(key))
+                .and_then(|n| n.attribute("value"))
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+
+        let Some(index) = metadata("plater_id").and_then(|id| id.parse().ok()) else {
+            continue;
+        };
+
+        plates.push(Plate {
+            index,
+            thumbnail: metadata("thumbnail_file"),
+            small_thumbnail: None,
+            top_thumbnail: metadata("top_file"),
+        });
+    }
+
+    plates.sort_by_key(|p| p.index);
+
+    Ok(plates)
+}
+
+fn read_string<R>(archive: &mut zip::ZipArchive<R>, filename: &str) -> Result<String>
+where
+    R: Read + Seek,
+{
+    let mut file = archive.by_name(filename)?;
+    let mut buffer = String::new();
+    file.read_to_string(&mut buffer)?;
+
+    Ok(buffer)
+}
+
+pub(crate) fn read_bytes<R>(archive: &mut zip::ZipArchive<R>, filename: &str) -> Result<Vec<u8>>
+where
+    R: Read + Seek,
+{
+    let mut file = archive.by_name(filename)?;
+    let mut buffer = vec![];
+    file.read_to_end(&mut buffer)?;
+
+    Ok(buffer)
+}
+
+pub(crate) fn read_picture<R>(archive: &mut zip::ZipArchive<R>, filename: &str) -> Result<Picture>
+where
+    R: Read + Seek,
+{
+    let buffer = read_bytes(archive, filename.trim_start_matches('/'))?;
+    let image = image::load_from_memory(&buffer)?;
+
+    Ok(Picture::from_img_buffer(image.to_rgba8()))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
     static PRUSA_TEST_FILE: &[u8] = include_bytes!("../test_models/prusa_test.3mf");
     static BAMBU_TEST_FILE: &[u8] = include_bytes!("../test_models/bambu_test.3mf");
+    static BAMBU_PRINT_FILE: &[u8] = include_bytes!("../test_models/bambu_test.gcode.3mf");
+
+    fn zip_archive(files: &[(&str, &[u8])]) -> std::io::Cursor<Vec<u8>> {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+        for (name, data) in files {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+            std::io::Write::write_all(&mut zip, data).unwrap();
+        }
+
+        let mut cursor = zip.finish().unwrap();
+        cursor.set_position(0);
+        cursor
+    }
+
+    fn bambu_plate_png(index: u32) -> Vec<u8> {
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(BAMBU_TEST_FILE)).unwrap();
+        read_bytes(&mut archive, &format!("Metadata/plate_{index}.png")).unwrap()
+    }
 
     #[test]
     pub fn test_extract_preview() {
@@ -57,4 +566,175 @@ mod test {
 
         assert!(preview.is_ok());
     }
+
+    #[test]
+    pub fn test_relationship_targets() {
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(BAMBU_TEST_FILE)).unwrap();
+
+        assert_eq!(
+            relationship_targets(&mut archive, "", THUMBNAIL_RELATIONSHIP),
+            vec!["Metadata/plate_1.png"]
+        );
+        assert_eq!(
+            relationship_targets(&mut archive, "/3D/3dmodel.model", MODEL_RELATIONSHIP),
+            vec!["3D/Objects/object_1.model", "3D/Objects/object_3.model"]
+        );
+        assert!(relationship_targets(&mut archive, "3D/3dmodel.model", THUMBNAIL_RELATIONSHIP).is_empty());
+    }
+
+    #[test]
+    pub fn test_object_thumbnail() {
+        // the only thumbnail is referenced by the model part, relative to '3D/'
+        let rels = format!(
+            r#"<Relationships><Relationship Id="rel0" Target="/3D/3dmodel.model" Type="{MODEL_RELATIONSHIP}"/></Relationships>"#
+        );
+        let model_rels = format!(
+            r#"<Relationships><Relationship Id="rel1" Target="../Metadata/object.png" Type="{THUMBNAIL_RELATIONSHIP}"/></Relationships>"#
+        );
+        let small_png = {
+            let mut archive = zip::ZipArchive::new(std::io::Cursor::new(BAMBU_TEST_FILE)).unwrap();
+            read_bytes(&mut archive, "Metadata/plate_1_small.png").unwrap()
+        };
+        let unreferenced_png = bambu_plate_png(1);
+        let files = [
+            ("_rels/.rels", rels.as_bytes()),
+            ("3D/_rels/3dmodel.model.rels", model_rels.as_bytes()),
+            ("Metadata/object.png", small_png.as_slice()),
+            // not referenced by any relationship
+            ("Metadata/unused.png", unreferenced_png.as_slice()),
+        ];
+
+        let mut archive = zip::ZipArchive::new(zip_archive(&files)).unwrap();
+        assert_eq!(
+            relationship_targets(&mut archive, "3D/3dmodel.model", THUMBNAIL_RELATIONSHIP),
+            vec!["Metadata/object.png"]
+        );
+
+        let preview = extract_preview(zip_archive(&files)).unwrap();
+        let expected = image::load_from_memory(&small_png).unwrap();
+        assert_eq!(preview.width(), expected.width());
+        assert_eq!(preview.data(), expected.to_rgba8().as_raw().as_slice());
+    }
+
+    #[test]
+    pub fn test_resolve_part_name() {
+        assert_eq!(
+            resolve_part_name("", "/Metadata/thumbnail.png"),
+            "Metadata/thumbnail.png"
+        );
+        assert_eq!(
+            resolve_part_name("3D", "/Metadata/thumbnail.jpg"),
+            "Metadata/thumbnail.jpg"
+        );
+        assert_eq!(resolve_part_name("3D", "Textures/object.png"), "3D/Textures/object.png");
+        assert_eq!(resolve_part_name("3D", "../Metadata/object.png"), "Metadata/object.png");
+    }
+
+    #[test]
+    pub fn test_list_plates() {
+        let plates = list_plates(std::io::Cursor::new(BAMBU_TEST_FILE)).unwrap();
+
+        assert_eq!(plates.len(), 2);
+        assert_eq!(plates[1].index, 2);
+        assert_eq!(plates[1].thumbnail.as_deref(), Some("Metadata/plate_2.png"));
+        assert_eq!(plates[1].small_thumbnail.as_deref(), Some("Metadata/plate_2_small.png"));
+        assert_eq!(plates[1].top_thumbnail.as_deref(), Some("Metadata/top_2.png"));
+
+        let plates = list_plates(std::io::Cursor::new(PRUSA_TEST_FILE)).unwrap();
+        assert!(plates.is_empty());
+    }
+
+    #[test]
+    pub fn test_extract_plate_preview() {
+        let preview = extract_plate_preview(std::io::Cursor::new(BAMBU_TEST_FILE), 2).unwrap();
+        assert_eq!(preview.width(), 512);
+
+        assert!(extract_plate_preview(std::io::Cursor::new(BAMBU_TEST_FILE), 3).is_err());
+    }
+
+    #[test]
+    pub fn test_extract_plates_grid() {
+        let grid = extract_plates_grid(std::io::Cursor::new(BAMBU_TEST_FILE)).unwrap();
+
+        assert_eq!(grid.width(), 1024);
+        assert_eq!(grid.height(), 512);
+    }
+
+    #[test]
+    pub fn test_plate_without_thumbnail() {
+        // the config lists plate 1, but only plate 2 has a thumbnail
+        let config = r#"<config>
+            <plate><metadata key="plater_id" value="1"/><metadata key="thumbnail_file" value="Metadata/plate_1.png"/></plate>
+            <plate><metadata key="plater_id" value="2"/><metadata key="thumbnail_file" value="Metadata/plate_2.png"/></plate>
+            </config>"#;
+        let png = bambu_plate_png(2);
+        let files = [
+            ("Metadata/model_settings.config", config.as_bytes()),
+            ("Metadata/plate_2.png", png.as_slice()),
+        ];
+
+        let grid = extract_plates_grid(zip_archive(&files)).unwrap();
+        assert_eq!(grid.width(), 512);
+
+        let preview = extract_preview(zip_archive(&files)).unwrap();
+        assert_eq!(preview.width(), 512);
+
+        assert!(extract_plates_grid(zip_archive(&files[..1])).is_err());
+    }
+
+    #[test]
+    pub fn test_is_print_package() {
+        assert!(is_print_package(std::io::Cursor::new(BAMBU_PRINT_FILE)));
+        assert!(!is_print_package(std::io::Cursor::new(BAMBU_TEST_FILE)));
+        assert!(!is_print_package(std::io::Cursor::new(PRUSA_TEST_FILE)));
+    }
+
+    #[test]
+    pub fn test_extract_print_previews() {
+        let previews = extract_print_previews(std::io::Cursor::new(BAMBU_PRINT_FILE)).unwrap();
+        let widths: Vec<_> = previews.iter().map(|p| p.width()).collect();
+        assert_eq!(widths, vec![512, 128, 32]);
+
+        let previews = list_print_previews(std::io::Cursor::new(BAMBU_PRINT_FILE)).unwrap();
+        assert_eq!(
+            previews[1],
+            PreviewInfo {
+                width: 128,
+                height: 128,
+                format: "png".to_string()
+            }
+        );
+    }
+
+    #[test]
+    pub fn test_extract_print_metadata() {
+        let metadata = extract_print_metadata(std::io::Cursor::new(BAMBU_PRINT_FILE)).unwrap();
+
+        assert_eq!(metadata.estimated_printing_time, Some(2301));
+        assert_eq!(metadata.filament_used_g, Some(6.54));
+        assert_eq!(metadata.filament_used_mm, Some(2190.0));
+        assert_eq!(metadata.filament_type.as_deref(), Some("PLA"));
+        assert_eq!(metadata.nozzle_diameter, Some(0.4));
+        assert_eq!(metadata.printer_model.as_deref(), Some("Bambu Lab X1 Carbon"));
+        // only in the gcode
+        assert_eq!(metadata.layer_height, Some(0.2));
+
+        let metadata = extract_print_metadata(std::io::Cursor::new(BAMBU_TEST_FILE)).unwrap();
+        assert_eq!(metadata, Metadata::default());
+    }
+
+    #[test]
+    pub fn test_slice_info_without_filament_usage() {
+        // the usage of the gcode is kept instead of reporting 0 mm
+        let mut metadata = Metadata {
+            filament_used_mm: Some(1500.0),
+            ..Default::default()
+        };
+        let slice_info =
+            r#"<config><plate><metadata key="index" value="1"/><filament id="1" type="PLA"/></plate></config>"#;
+        apply_slice_info(slice_info, 1, &mut metadata).unwrap();
+
+        assert_eq!(metadata.filament_type.as_deref(), Some("PLA"));
+        assert_eq!(metadata.filament_used_mm, Some(1500.0));
+    }
 }
@@ -0,0 +1,87 @@
+use anyhow::{bail, Result};
+use std::{
+    io::{Read, Seek},
+    path::Path,
+};
+
+use crate::{
+    gcode,
+    picture::Picture,
+    threemf::{read_bytes, read_picture, relationship_targets, THUMBNAIL_RELATIONSHIP},
+};
+
+const GCODE_RELATIONSHIP: &str = "http://schemas.ultimaker.org/package/2018/relationships/gcode";
+
+pub fn extract_preview_from_file<P: AsRef<Path>>(filename: P) -> Result<Picture> {
+    let file = std::fs::File::open(filename)?;
+    extract_preview(file)
+}
+
+/// Extracts the thumbnail of an Ultimaker Format Package ('.ufp')
+///
+/// Packages without a thumbnail fall back to the largest preview embedded into the gcode.
+pub fn extract_preview<R>(r: R) -> Result<Picture>
+where
+    R: Read + Seek,
+{
+    let mut archive = zip::ZipArchive::new(r)?;
+
+    // Cura stores the thumbnail in 'Metadata/thumbnail.png' and references it in '_rels/.rels'
+    let mut thumbnails = relationship_targets(&mut archive, "", THUMBNAIL_RELATIONSHIP);
+    thumbnails.push("Metadata/thumbnail.png".to_string());
+
+    for target in thumbnails {
+        if let Ok(picture) = read_picture(&mut archive, &target) {
+            return Ok(picture);
+        }
+    }
+
+    // the gcode is usually stored in '3D/model.gcode'
+    let mut gcodes = relationship_targets(&mut archive, "", GCODE_RELATIONSHIP);
+    gcodes.push("3D/model.gcode".to_string());
+
+    for target in gcodes {
+        if let Ok(data) = read_bytes(&mut archive, &target) {
+            let previews = gcode::extract_previews_from_data(&data).unwrap_or_default();
+
+            if let Some(picture) = gcode::select_preview(previews, u32::MAX, u32::MAX) {
+                return Ok(picture);
+            }
+        }
+    }
+
+    bail!("Cannot find thumbnail in ufp")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{Cursor, Write};
+
+    static CURA_TEST_FILE: &[u8] = include_bytes!("../test_models/cura_test.ufp");
+
+    #[test]
+    pub fn test_extract_preview() {
+        let preview = extract_preview(Cursor::new(CURA_TEST_FILE)).unwrap();
+
+        assert_eq!(preview.width(), 128);
+    }
+
+    #[test]
+    pub fn test_extract_preview_from_gcode() {
+        // repackage the gcode without the thumbnail
+        let mut archive = zip::ZipArchive::new(Cursor::new(CURA_TEST_FILE)).unwrap();
+        let gcode = read_bytes(&mut archive, "3D/model.gcode").unwrap();
+
+        let mut writer = zip::ZipWriter::new(Cursor::new(vec![]));
+        writer
+            .start_file("3D/model.gcode", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(&gcode).unwrap();
+        let package = writer.finish().unwrap();
+
+        let preview = extract_preview(Cursor::new(package.into_inner())).unwrap();
+
+        assert_eq!(preview.width(), 32);
+    }
+}
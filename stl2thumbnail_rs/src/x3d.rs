@@ -0,0 +1,842 @@
+use std::{collections::HashMap, io::Read, path::Path};
+
+use anyhow::{bail, Result};
+use flate2::read::GzDecoder;
+
+use crate::stl::mesh::{transform_point, Facet, Mat4, Mesh, Triangle, Vec3};
+
+// ref: https://www.web3d.org/documents/specifications/19775-1/V3.3/Part01/components/geometry3D.html#IndexedFaceSet
+//      https://www.web3d.org/documents/specifications/14772/V2.0/part1/nodesRef.html#IndexedFaceSet
+
+/// Deepest nesting of nodes, deeper files are rejected instead of overflowing the stack
+const MAX_DEPTH: usize = 256;
+
+pub fn read_mesh_from_file<P: AsRef<Path>>(filename: P) -> Result<Mesh> {
+    let data = std::fs::read(filename)?;
+    read_mesh_from_data(&data)
+}
+
+/// Reads the `IndexedFaceSet`s of an X3D (XML or classic encoding) or VRML97 file
+///
+/// Gzip compressed files ('.x3dz', '.wrl.gz') are supported.
+/// The model is converted from Y-up to Z-up.
+pub fn read_mesh_from_data(data: &[u8]) -> Result<Mesh> {
+    let mut content = String::new();
+    if data.starts_with(&[0x1F, 0x8B]) {
+        GzDecoder::new(data).read_to_string(&mut content)?;
+    } else {
+        content = String::from_utf8_lossy(data).into_owned();
+    }
+
+    // Y-up to Z-up
+    let transform = glm::rotation(90.0f32.to_radians(), &Vec3::new(1.0, 0.0, 0.0));
+    let mut triangles = Mesh::default();
+
+    if content.trim_start_matches('\u{FEFF}').trim_start().starts_with('<') {
+        // roxmltree recurses for every element
+        if xml_depth(&content) > MAX_DEPTH {
+            bail!("nodes are nested too deeply");
+        }
+
+        // X3D files usually come with a DOCTYPE
+        let options = roxmltree::ParsingOptions {
+            allow_dtd: true,
+            ..Default::default()
+        };
+        let doc = roxmltree::Document::parse_with_options(&content, options)?;
+        let defs = doc
+            .descendants()
+            .filter_map(|n| Some((n.attribute("DEF")?, n)))
+            .collect();
+
+        read_xml_node(&doc.root_element(), &transform, &defs, &mut vec![], &mut triangles)?;
+    } else {
+        let mut parser = VrmlParser::new(&content);
+        for node in parser.parse()? {
+            read_vrml_node(&node, &transform, &mut triangles)?;
+        }
+    }
+
+    Ok(triangles)
+}
+
+/// The geometry and appearance of a `Shape` with an `IndexedFaceSet`
+#[derive(Debug, Default)]
+struct FaceSet {
+    points: Vec<f32>,
+    coord_index: Vec<i32>,
+    colors: Vec<f32>,
+    color_index: Vec<i32>,
+    color_per_vertex: bool,
+    ccw: bool,
+    diffuse_color: Option<Vec3>,
+}
+
+impl FaceSet {
+    fn triangulate(&self, transform: &Mat4, triangles: &mut Mesh) -> Result<()> {
+        let points: Vec<Vec3> = self
+            .points
+            .chunks_exact(3)
+            .map(|p| transform_point(transform, &Vec3::new(p[0], p[1], p[2])))
+            .collect();
+        let colors: Vec<Vec3> = self
+            .colors
+            .chunks_exact(3)
+            .map(|c| Vec3::new(c[0], c[1], c[2]))
+            .collect();
+
+        // faces are separated by '-1'
+        let faces = self.coord_index.split(|&i| i < 0).filter(|face| !face.is_empty());
+        let mut offset = 0; // position of the face in 'coordIndex'
+
+        for (face_index, face) in faces.enumerate() {
+            // skip the separators
+            while self.coord_index.get(offset).is_some_and(|&i| i < 0) {
+                offset += 1;
+            }
+
+            let indices: Vec<usize> = face.iter().map(|&i| i as usize).collect();
+            if indices.iter().any(|&i| i >= points.len()) {
+                bail!("coordinate index out of range");
+            }
+
+            // 'colorIndex' defaults to 'coordIndex' (per vertex) or the face index (per face)
+            let color_at = |corner: usize| -> Option<Vec3> {
+                let index = if self.color_per_vertex {
+                    match self.color_index.get(offset + corner) {
+                        Some(&i) => usize::try_from(i).ok()?,
+                        None => indices[corner],
+                    }
+                } else {
+                    match self.color_index.get(face_index) {
+                        Some(&i) => usize::try_from(i).ok()?,
+                        None => face_index,
+                    }
+                };
+                colors.get(index).copied()
+            };
+
+            // triangulate the polygon as a fan
+            for i in 1..indices.len().saturating_sub(1) {
+                let mut corners = [0, i, i + 1];
+                if !self.ccw {
+                    corners.swap(1, 2);
+                }
+
+                let vertices = corners.map(|c| points[indices[c]]);
+                let normal = (vertices[1] - vertices[0])
+                    .cross(&(vertices[2] - vertices[0]))
+                    .normalize();
+
+                let mut facet = Facet::from(Triangle::new(vertices, normal));
+                if let [Some(c0), Some(c1), Some(c2)] = corners.map(color_at) {
+                    facet = facet.with_colors([c0, c1, c2]);
+                } else if let Some(color) = self.diffuse_color {
+                    facet = facet.with_colors([color; 3]);
+                }
+
+                triangles.push(facet);
+            }
+
+            offset += face.len();
+        }
+
+        Ok(())
+    }
+}
+
+/// The transform of a `Transform` node: T * C * R * S * -C
+fn node_transform(translation: &[f32], center: &[f32], rotation: &[f32], scale: &[f32]) -> Mat4 {
+    let vec3 = |v: &[f32], default: f32| match v {
+        [x, y, z, ..] => Vec3::new(*x, *y, *z),
+        _ => Vec3::new(default, default, default),
+    };
+
+    let center = vec3(center, 0.0);
+    let rotation = match rotation {
+        [x, y, z, angle, ..] if *angle != 0.0 => glm::rotation(*angle, &Vec3::new(*x, *y, *z)),
+        _ => Mat4::identity(),
+    };
+
+    glm::translation(&vec3(translation, 0.0))
+        * glm::translation(&center)
+        * rotation
+        * glm::scaling(&vec3(scale, 1.0))
+        * glm::translation(&-center)
+}
+
+// X3D (XML encoding)
+
+/// `path` holds the nodes being read, a node on it that is reached again through `USE` is a cycle
+fn read_xml_node<'a, 'input>(
+    node: &roxmltree::Node<'a, 'input>,
+    transform: &Mat4,
+    defs: &HashMap<&str, roxmltree::Node<'a, 'input>>,
+    path: &mut Vec<roxmltree::NodeId>,
+    triangles: &mut Mesh,
+) -> Result<()> {
+    if path.contains(&node.id()) {
+        bail!("node '{}' uses itself", node.attribute("DEF").unwrap_or_default());
+    }
+    if path.len() >= MAX_DEPTH {
+        bail!("nodes are nested too deeply");
+    }
+    path.push(node.id());
+
+    for child in node.children().filter(|n| n.is_element()) {
+        let child = resolve_xml_node(child, defs);
+
+        match child.tag_name().name() {
+            "Transform" => {
+                let transform = transform
+                    * node_transform(
+                        &xml_floats(&child, "translation"),
+                        &xml_floats(&child, "center"),
+                        &xml_floats(&child, "rotation"),
+                        &xml_floats(&child, "scale"),
+                    );
+                read_xml_node(&child, &transform, defs, path, triangles)?;
+            }
+            "Shape" => read_xml_shape(&child, transform, defs, triangles)?,
+            // protos, scripts and metadata do not contain any geometry
+            "ProtoDeclare" | "ExternProtoDeclare" | "Script" | "head" => (),
+            _ => read_xml_node(&child, transform, defs, path, triangles)?,
+        }
+    }
+
+    path.pop();
+    Ok(())
+}
+
+fn read_xml_shape(
+    shape: &roxmltree::Node,
+    transform: &Mat4,
+    defs: &HashMap<&str, roxmltree::Node>,
+    triangles: &mut Mesh,
+) -> Result<()> {
+    let Some(geometry) = find_xml_child(shape, "IndexedFaceSet", defs) else {
+        return Ok(());
+    };
+
+    let diffuse_color = find_xml_child(shape, "Appearance", defs)
+        .and_then(|appearance| find_xml_child(&appearance, "Material", defs))
+        .map(|material| xml_floats(&material, "diffuseColor"))
+        .and_then(|c| (c.len() >= 3).then(|| Vec3::new(c[0], c[1], c[2])));
+
+    let face_set = FaceSet {
+        points: find_xml_child(&geometry, "Coordinate", defs)
+            .map(|n| xml_floats(&n, "point"))
+            .unwrap_or_default(),
+        coord_index: xml_indices(&geometry, "coordIndex")?,
+        colors: find_xml_child(&geometry, "Color", defs)
+            .map(|n| xml_floats(&n, "color"))
+            .unwrap_or_default(),
+        color_index: xml_indices(&geometry, "colorIndex")?,
+        color_per_vertex: geometry.attribute("colorPerVertex") != Some("false"),
+        ccw: geometry.attribute("ccw") != Some("false"),
+        diffuse_color,
+    };
+
+    face_set.triangulate(transform, triangles)
+}
+
+fn find_xml_child<'a, 'input>(
+    node: &roxmltree::Node<'a, 'input>,
+    tag: &str,
+    defs: &HashMap<&str, roxmltree::Node<'a, 'input>>,
+) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children()
+        .filter(|n| n.is_element())
+        .map(|n| resolve_xml_node(n, defs))
+        .find(|n| n.has_tag_name(tag))
+}
+
+fn resolve_xml_node<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    defs: &HashMap<&str, roxmltree::Node<'a, 'input>>,
+) -> roxmltree::Node<'a, 'input> {
+    node.attribute("USE")
+        .and_then(|name| defs.get(name))
+        .copied()
+        .unwrap_or(node)
+}
+
+/// Deepest nesting of elements, comments and CDATA sections are skipped
+fn xml_depth(content: &str) -> usize {
+    let mut depth = 0usize;
+    let mut max_depth = 0;
+    let mut rest = content;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+
+        if let Some(comment) = rest.strip_prefix("!--") {
+            rest = comment.find("-->").map_or("", |i| &comment[i + 3..]);
+        } else if let Some(cdata) = rest.strip_prefix("![CDATA[") {
+            rest = cdata.find("]]>").map_or("", |i| &cdata[i + 3..]);
+        } else if rest.starts_with(['!', '?']) {
+            // declarations, processing instructions
+        } else if let Some(tag) = rest.strip_prefix('/') {
+            depth = depth.saturating_sub(1);
+            rest = tag;
+        } else {
+            // find the end of the tag, '>' may appear in attribute values
+            let mut quote = None;
+            let mut end = rest.len();
+            for (i, c) in rest.char_indices() {
+                match (quote, c) {
+                    (None, '"' | '\'') => quote = Some(c),
+                    (Some(q), c) if q == c => quote = None,
+                    (None, '>') => {
+                        end = i;
+                        break;
+                    }
+                    _ => (),
+                }
+            }
+
+            if !rest[..end].ends_with('/') {
+                depth += 1;
+                max_depth = max_depth.max(depth);
+            }
+            rest = &rest[end..];
+        }
+    }
+
+    max_depth
+}
+
+fn xml_floats(node: &roxmltree::Node, attribute: &str) -> Vec<f32> {
+    node.attribute(attribute)
+        .unwrap_or_default()
+        .split(|c: char| c.is_ascii_whitespace() || c == ',')
+        .filter_map(|v| v.parse().ok())
+        .collect()
+}
+
+fn xml_indices(node: &roxmltree::Node, attribute: &str) -> Result<Vec<i32>> {
+    node.attribute(attribute)
+        .unwrap_or_default()
+        .split(|c: char| c.is_ascii_whitespace() || c == ',')
+        .filter(|v| !v.is_empty())
+        .map(|v| match v.parse() {
+            Ok(index) => Ok(index),
+            Err(_) => bail!("invalid index '{v}'"),
+        })
+        .collect()
+}
+
+// VRML97 and X3D (classic encoding)
+
+#[derive(Debug, Clone, PartialEq)]
+enum VrmlValue {
+    Node(VrmlNode),
+    Nodes(Vec<VrmlNode>),
+    Numbers(Vec<f64>), // exact for all indices
+    Word(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct VrmlNode {
+    kind: String,
+    fields: Vec<(String, VrmlValue)>,
+}
+
+impl VrmlNode {
+    fn field(&self, name: &str) -> Option<&VrmlValue> {
+        self.fields.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+
+    fn node(&self, name: &str) -> Option<&VrmlNode> {
+        match self.field(name) {
+            Some(VrmlValue::Node(node)) => Some(node),
+            _ => None,
+        }
+    }
+
+    fn numbers(&self, name: &str) -> Vec<f32> {
+        match self.field(name) {
+            Some(VrmlValue::Numbers(numbers)) => numbers.iter().map(|&n| n as f32).collect(),
+            _ => vec![],
+        }
+    }
+
+    fn indices(&self, name: &str) -> Result<Vec<i32>> {
+        let Some(VrmlValue::Numbers(numbers)) = self.field(name) else {
+            return Ok(vec![]);
+        };
+
+        numbers
+            .iter()
+            .map(|&n| match i32::try_from(n as i64) {
+                Ok(index) if index as f64 == n => Ok(index),
+                _ => bail!("invalid index '{n}'"),
+            })
+            .collect()
+    }
+
+    fn is_false(&self, name: &str) -> bool {
+        matches!(self.field(name), Some(VrmlValue::Word(word)) if word == "FALSE")
+    }
+
+    fn children(&self) -> impl Iterator<Item = &VrmlNode> {
+        self.fields.iter().flat_map(|(_, value)| match value {
+            VrmlValue::Node(node) => std::slice::from_ref(node),
+            VrmlValue::Nodes(nodes) => nodes.as_slice(),
+            _ => &[],
+        })
+    }
+}
+
+fn read_vrml_node(node: &VrmlNode, transform: &Mat4, triangles: &mut Mesh) -> Result<()> {
+    match node.kind.as_str() {
+        "Transform" => {
+            let transform = transform
+                * node_transform(
+                    &node.numbers("translation"),
+                    &node.numbers("center"),
+                    &node.numbers("rotation"),
+                    &node.numbers("scale"),
+                );
+            for child in node.children() {
+                read_vrml_node(child, &transform, triangles)?;
+            }
+        }
+        "Shape" => {
+            let Some(geometry) = node.node("geometry").filter(|n| n.kind == "IndexedFaceSet") else {
+                return Ok(());
+            };
+
+            let diffuse_color = node
+                .node("appearance")
+                .and_then(|appearance| appearance.node("material"))
+                .map(|material| material.numbers("diffuseColor"))
+                .and_then(|c| (c.len() >= 3).then(|| Vec3::new(c[0], c[1], c[2])));
+
+            let face_set = FaceSet {
+                points: geometry.node("coord").map(|n| n.numbers("point")).unwrap_or_default(),
+                coord_index: geometry.indices("coordIndex")?,
+                colors: geometry.node("color").map(|n| n.numbers("color")).unwrap_or_default(),
+                color_index: geometry.indices("colorIndex")?,
+                color_per_vertex: !geometry.is_false("colorPerVertex"),
+                ccw: !geometry.is_false("ccw"),
+                diffuse_color,
+            };
+
+            face_set.triangulate(transform, triangles)?;
+        }
+        _ => {
+            for child in node.children() {
+                read_vrml_node(child, transform, triangles)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+struct VrmlParser<'a> {
+    tokens: Vec<&'a str>,
+    pos: usize,
+    depth: usize,
+    defs: HashMap<&'a str, VrmlNode>,
+}
+
+impl<'a> VrmlParser<'a> {
+    fn new(content: &'a str) -> Self {
+        Self {
+            tokens: tokenize(content),
+            pos: 0,
+            depth: 0,
+            defs: HashMap::new(),
+        }
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Result<&'a str> {
+        let Some(token) = self.peek() else {
+            bail!("unexpected end of file")
+        };
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<()> {
+        let token = self.next()?;
+        if token != expected {
+            bail!("expected '{expected}', found '{token}'");
+        }
+        Ok(())
+    }
+
+    /// Parses the top level nodes
+    fn parse(&mut self) -> Result<Vec<VrmlNode>> {
+        let mut nodes = vec![];
+
+        while let Some(token) = self.peek() {
+            match token {
+                // statements without geometry
+                "PROFILE" | "COMPONENT" | "UNIT" | "META" | "ROUTE" | "IMPORT" | "EXPORT" | "PROTO" | "EXTERNPROTO" => {
+                    self.skip_statement()?
+                }
+                _ => nodes.push(self.parse_node()?),
+            }
+        }
+
+        Ok(nodes)
+    }
+
+    fn skip_statement(&mut self) -> Result<()> {
+        match self.next()? {
+            "PROFILE" | "COMPONENT" => self.pos += 1,
+            "META" => self.pos += 2,
+            "UNIT" => self.pos += 3,
+            // ROUTE a.b TO c.d
+            "ROUTE" => self.pos += 3,
+            // IMPORT a.b AS c, EXPORT a AS b
+            "IMPORT" | "EXPORT" => {
+                self.pos += 1;
+                if self.peek() == Some("AS") {
+                    self.pos += 2;
+                }
+            }
+            // PROTO name [ interface ] { body }
+            "PROTO" => {
+                self.pos += 1;
+                self.skip_block("[", "]")?;
+                self.skip_block("{", "}")?;
+            }
+            // EXTERNPROTO name [ interface ] urls
+            "EXTERNPROTO" => {
+                self.pos += 1;
+                self.skip_block("[", "]")?;
+                if self.peek() == Some("[") {
+                    self.skip_block("[", "]")?;
+                } else {
+                    self.pos += 1;
+                }
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn skip_block(&mut self, open: &str, close: &str) -> Result<()> {
+        self.expect(open)?;
+
+        let mut depth = 1;
+        while depth > 0 {
+            let token = self.next()?;
+            if token == open {
+                depth += 1;
+            } else if token == close {
+                depth -= 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_node(&mut self) -> Result<VrmlNode> {
+        let mut kind = self.next()?;
+
+        if kind == "USE" {
+            let name = self.next()?;
+            let Some(node) = self.defs.get(name) else {
+                bail!("unknown node '{name}'")
+            };
+            return Ok(node.clone());
+        }
+
+        let mut def = None;
+        if kind == "DEF" {
+            def = Some(self.next()?);
+            kind = self.next()?;
+        }
+
+        if self.depth >= MAX_DEPTH {
+            bail!("nodes are nested too deeply");
+        }
+        self.depth += 1;
+
+        self.expect("{")?;
+
+        let mut fields = vec![];
+        loop {
+            match self.next()? {
+                "}" => break,
+                "ROUTE" => {
+                    self.pos -= 1;
+                    self.skip_statement()?;
+                }
+                // interface declarations of scripts
+                kind @ ("field" | "eventIn" | "eventOut" | "exposedField" | "inputOnly" | "outputOnly"
+                | "initializeOnly" | "inputOutput") => {
+                    // type and name, events have no value
+                    self.pos += 2;
+                    if !matches!(kind, "eventIn" | "eventOut" | "inputOnly" | "outputOnly") {
+                        self.parse_value()?;
+                    }
+                }
+                name => {
+                    let value = self.parse_value()?;
+                    fields.push((name.to_string(), value));
+                }
+            }
+        }
+
+        let node = VrmlNode {
+            kind: kind.to_string(),
+            fields,
+        };
+
+        if let Some(def) = def {
+            self.defs.insert(def, node.clone());
+        }
+
+        self.depth -= 1;
+        Ok(node)
+    }
+
+    fn parse_value(&mut self) -> Result<VrmlValue> {
+        let Some(token) = self.peek() else {
+            bail!("unexpected end of file")
+        };
+
+        if token == "[" {
+            self.pos += 1;
+
+            let mut numbers = vec![];
+            let mut nodes = vec![];
+            let mut words = vec![];
+
+            loop {
+                match self.peek() {
+                    Some("]") => {
+                        self.pos += 1;
+                        break;
+                    }
+                    Some("USE" | "DEF") => nodes.push(self.parse_node()?),
+                    Some(_) if self.tokens.get(self.pos + 1) == Some(&"{") => nodes.push(self.parse_node()?),
+                    Some(token) if parse_number(token).is_some() => {
+                        numbers.extend(parse_number(token));
+                        self.pos += 1;
+                    }
+                    // strings, booleans
+                    Some(token) if is_word(token) => {
+                        words.push(token);
+                        self.pos += 1;
+                    }
+                    Some(token) => bail!("unexpected '{token}'"),
+                    None => bail!("unexpected end of file"),
+                }
+            }
+
+            return Ok(if !nodes.is_empty() {
+                VrmlValue::Nodes(nodes)
+            } else if !words.is_empty() {
+                VrmlValue::Word(words.join(" "))
+            } else {
+                VrmlValue::Numbers(numbers)
+            });
+        }
+
+        if parse_number(token).is_some() {
+            let mut numbers = vec![];
+            while let Some(number) = self.peek().and_then(parse_number) {
+                numbers.push(number);
+                self.pos += 1;
+            }
+            return Ok(VrmlValue::Numbers(numbers));
+        }
+
+        let is_node = matches!(token, "USE" | "DEF") || self.tokens.get(self.pos + 1) == Some(&"{");
+        if is_node {
+            return Ok(VrmlValue::Node(self.parse_node()?));
+        }
+
+        // TRUE, FALSE, NULL, strings
+        self.pos += 1;
+        Ok(VrmlValue::Word(token.to_string()))
+    }
+}
+
+fn is_word(token: &str) -> bool {
+    !matches!(token, "{" | "}" | "[" | "]")
+}
+
+fn parse_number(token: &str) -> Option<f64> {
+    // hexadecimal numbers are used by images
+    match token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok().map(|v| v as f64),
+        None => token.parse().ok(),
+    }
+}
+
+/// Splits the content into words, strings and brackets, commas and comments are dropped
+fn tokenize(content: &str) -> Vec<&str> {
+    let mut tokens = vec![];
+    let bytes = content.as_bytes();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'#' => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'"' => {
+                let start = i;
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                i = (i + 1).min(bytes.len());
+                tokens.push(&content[start..i]);
+            }
+            b'{' | b'}' | b'[' | b']' => {
+                tokens.push(&content[i..i + 1]);
+                i += 1;
+            }
+            c if c.is_ascii_whitespace() || c == b',' => i += 1,
+            _ => {
+                let start = i;
+                while i < bytes.len()
+                    && !bytes[i].is_ascii_whitespace()
+                    && !matches!(bytes[i], b',' | b'{' | b'}' | b'[' | b']' | b'"' | b'#')
+                {
+                    i += 1;
+                }
+                tokens.push(&content[start..i]);
+            }
+        }
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const QUAD_WRL: &[u8] = include_bytes!("../test_models/quad.wrl");
+    const QUAD_X3D: &[u8] = include_bytes!("../test_models/quad.x3d");
+
+    fn assert_vec3_eq(a: Vec3, b: Vec3) {
+        assert!((a - b).norm() < 1e-5, "{a} != {b}");
+    }
+
+    #[test]
+    fn read_vrml_test() {
+        let mesh = read_mesh_from_data(QUAD_WRL).unwrap();
+
+        // the second shape re-uses the geometry of the first one
+        assert_eq!(mesh.len(), 4);
+
+        // Y-up to Z-up
+        assert_vec3_eq(mesh[0].vertices[1], Vec3::new(1.0, 0.0, 0.0));
+        assert_vec3_eq(mesh[0].vertices[2], Vec3::new(1.0, 1.0, 0.0));
+        assert_vec3_eq(mesh[0].normal, Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(mesh.facet(0).colors, Some([Vec3::new(1.0, 0.0, 0.0); 3]));
+
+        // translated by 2 along y (z in Z-up)
+        assert_vec3_eq(mesh[2].vertices[0], Vec3::new(0.0, 0.0, 2.0));
+    }
+
+    #[test]
+    fn read_x3d_test() {
+        let mesh = read_mesh_from_data(QUAD_X3D).unwrap();
+
+        assert_eq!(mesh.len(), 4);
+        assert_vec3_eq(mesh[0].vertices[2], Vec3::new(1.0, 1.0, 0.0));
+        assert_vec3_eq(mesh[2].vertices[0], Vec3::new(0.0, 0.0, 2.0));
+
+        // per vertex colors
+        let colors = mesh.facet(0).colors.unwrap();
+        assert_eq!(colors[0], Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(colors[1], Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn tokenize_test() {
+        assert_eq!(
+            tokenize("Shape { # comment\n url \"a b\" point [0 0 0, 1 1 1] }"),
+            vec!["Shape", "{", "url", "\"a b\"", "point", "[", "0", "0", "0", "1", "1", "1", "]", "}"]
+        );
+    }
+
+    #[test]
+    fn read_invalid_index_test() {
+        let x3d = r#"<X3D><Scene><Shape>
+            <IndexedFaceSet coordIndex="0 1.5 2 -1"><Coordinate point="0 0 0 1 0 0 1 1 0"/></IndexedFaceSet>
+            </Shape></Scene></X3D>"#;
+        assert!(read_mesh_from_data(x3d.as_bytes()).is_err());
+
+        let wrl = "#VRML V2.0 utf8
+            Shape { geometry IndexedFaceSet { coord Coordinate { point [0 0 0, 1 0 0, 1 1 0] } coordIndex [0 1.5 2 -1] } }";
+        assert!(read_mesh_from_data(wrl.as_bytes()).is_err());
+
+        let wrl = wrl.replace("1.5", "1");
+        assert_eq!(read_mesh_from_data(wrl.as_bytes()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn read_use_cycle_test() {
+        let x3d = r#"<X3D><Scene><Transform DEF="A"><Transform USE="A"/></Transform></Scene></X3D>"#;
+        assert!(read_mesh_from_data(x3d.as_bytes()).is_err());
+
+        let x3d = r#"<X3D><Scene>
+            <Transform DEF="A"><Transform USE="B"/></Transform>
+            <Transform DEF="B"><Transform USE="A"/></Transform>
+            </Scene></X3D>"#;
+        assert!(read_mesh_from_data(x3d.as_bytes()).is_err());
+
+        // using a node twice is fine
+        let x3d = r#"<X3D><Scene>
+            <Shape DEF="S"><IndexedFaceSet coordIndex="0 1 2 -1"><Coordinate point="0 0 0 1 0 0 1 1 0"/></IndexedFaceSet></Shape>
+            <Transform><Shape USE="S"/></Transform>
+            </Scene></X3D>"#;
+        assert_eq!(read_mesh_from_data(x3d.as_bytes()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn xml_depth_test() {
+        assert_eq!(xml_depth("<?xml version='1.0'?><X3D><Scene/></X3D>"), 1);
+        assert_eq!(
+            xml_depth("<X3D><!-- <a><b> --><Scene a='>'><![CDATA[<c>]]><Shape/></Scene></X3D>"),
+            2
+        );
+        assert_eq!(xml_depth(std::str::from_utf8(QUAD_X3D).unwrap()), 5);
+    }
+
+    #[test]
+    fn read_deeply_nested_test() {
+        let depth = MAX_DEPTH - 10;
+        let x3d = format!("<X3D>{}{}</X3D>", "<Group>".repeat(depth), "</Group>".repeat(depth));
+        assert!(read_mesh_from_data(x3d.as_bytes()).is_ok());
+
+        let wrl = format!(
+            "#VRML V2.0 utf8\n{}{}",
+            "Group { children [ ".repeat(depth),
+            "] } ".repeat(depth)
+        );
+        assert!(read_mesh_from_data(wrl.as_bytes()).is_ok());
+
+        let depth = 100_000;
+
+        let x3d = format!("<X3D>{}{}</X3D>", "<Group>".repeat(depth), "</Group>".repeat(depth));
+        assert!(read_mesh_from_data(x3d.as_bytes()).is_err());
+
+        let wrl = format!(
+            "#VRML V2.0 utf8\n{}{}",
+            "Group { children [ ".repeat(depth),
+            "] } ".repeat(depth)
+        );
+        assert!(read_mesh_from_data(wrl.as_bytes()).is_err());
+    }
+}